@@ -0,0 +1,47 @@
+// Playground logic, kept separate from the page so that it can be tested without a browser.
+
+/**
+ * Formats the JSON returned by the compiler for the output and diagnostics panes.
+ *
+ * @param {string} json
+ * @returns {{ output: string, diagnostics: string }}
+ */
+export function render(json) {
+    const result = JSON.parse(json);
+
+    if (result.error) {
+        return { output: "", diagnostics: result.error };
+    }
+
+    const diagnostics = result.diagnostics
+        .map(({ severity, message, span }) => `${span.start_line}:${span.start_column}: ${severity}: ${message}`)
+        .join("\n");
+
+    return { output: result.code ?? "", diagnostics };
+}
+
+/**
+ * Compiles the source whenever it or the options change.
+ *
+ * @param {Document} document
+ * @param {(source: string, options: string) => string} compile
+ */
+export function attach(document, compile) {
+    const element = (id) => document.getElementById(id);
+    const source = element("source");
+    const target = element("target");
+    const comments = element("comments");
+
+    const update = () => {
+        const options = JSON.stringify({ target: target.value, comments: comments.value });
+        const { output, diagnostics } = render(compile(source.value, options));
+        element("output").textContent = output;
+        element("diagnostics").textContent = diagnostics;
+    };
+
+    for (const input of [source, target, comments]) {
+        input.addEventListener("input", update);
+    }
+
+    update();
+}
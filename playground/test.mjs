@@ -0,0 +1,66 @@
+// Headless tests for the playground: `node --test playground/`
+//
+// The page is tested against the real compiler if it has been built into `pkg/`, see README.md.
+
+import assert from "node:assert/strict";
+import { existsSync, readFileSync } from "node:fs";
+import { test } from "node:test";
+
+import { attach, render } from "./playground.js";
+
+const pkg = new URL("./pkg/", import.meta.url);
+
+function fakeDocument(values) {
+    const elements = {};
+    for (const id of ["source", "target", "comments", "output", "diagnostics"]) {
+        elements[id] = {
+            value: values[id] ?? "",
+            textContent: "",
+            listeners: [],
+            addEventListener(event, listener) {
+                this.listeners.push(listener);
+            },
+        };
+    }
+    return { elements, getElementById: (id) => elements[id] };
+}
+
+test("renders output and diagnostics", () => {
+    const json = JSON.stringify({
+        code: "$.print(1)\n",
+        diagnostics: [{ severity: "warning", message: "Unused variable", span: { start_line: 1, start_column: 3 } }],
+    });
+    assert.deepEqual(render(json), { output: "$.print(1)\n", diagnostics: "1:3: warning: Unused variable" });
+
+    assert.deepEqual(render(JSON.stringify({ code: null, diagnostics: [], error: "Unknown target" })), {
+        output: "",
+        diagnostics: "Unknown target",
+    });
+});
+
+test("compiles on input", () => {
+    const calls = [];
+    const compile = (source, options) => {
+        calls.push([source, JSON.parse(options)]);
+        return JSON.stringify({ code: source.toUpperCase(), diagnostics: [] });
+    };
+
+    const document = fakeDocument({ source: "a", target: "0.9", comments: "none" });
+    attach(document, compile);
+    assert.equal(document.elements.output.textContent, "A");
+
+    document.elements.source.value = "b";
+    document.elements.source.listeners.forEach((listener) => listener());
+    assert.equal(document.elements.output.textContent, "B");
+    assert.deepEqual(calls.at(-1), ["b", { target: "0.9", comments: "none" }]);
+});
+
+test("works with the compiler", { skip: !existsSync(new URL("scgt_bg.wasm", pkg)) && "pkg/ has not been built" }, async () => {
+    const { default: init, compile } = await import(new URL("scgt.js", pkg));
+    await init({ module_or_path: readFileSync(new URL("scgt_bg.wasm", pkg)) });
+
+    const document = fakeDocument({ source: "a!2;$a", target: "0.8", comments: "none" });
+    attach(document, compile);
+    assert.equal(document.elements.output.textContent, "let a = 2\n\n$.print(a)\n");
+    assert.equal(document.elements.diagnostics.textContent, "");
+});
@@ -0,0 +1,172 @@
+use chumsky::span::SimpleSpan;
+
+#[derive(Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: SimpleSpan,
+}
+
+#[derive(Clone)]
+pub enum ExprKind {
+    /// Number literal, kept as written in the source.
+    Number(String),
+    /// String literal with all escapes already resolved.
+    String(String),
+    TypeIndicator(String),
+    Constant(Constant),
+    /// `I`, `J` or `K`
+    LoopVariable(char),
+    Ident(String),
+
+    ShortMultiplication(String, Box<Expr>),
+    Invert(Box<Expr>),
+    Print(Box<Expr>),
+    OnTouch(Box<Expr>),
+
+    Block(Vec<Expr>),
+    TriggerFunction(Vec<Expr>),
+    Macro { params: Vec<String>, body: Vec<Expr> },
+    Loop(Vec<Expr>),
+
+    Assign { target: Box<Expr>, value: Box<Expr> },
+    MemberAccess { value: Box<Expr>, name: String },
+    MacroCallNoArgs(Box<Expr>),
+}
+
+/// Single uppercase letters that stand for a fixed SPWN value.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Constant {
+    EmptyArray,
+    Block,
+    Color,
+    Item,
+    False,
+    Group,
+    Null,
+    EmptyString,
+    True,
+}
+
+impl Constant {
+    /// The letter that stands for the constant in SCGT code.
+    pub fn letter(&self) -> char {
+        match self {
+            Self::EmptyArray => 'A',
+            Self::Block => 'B',
+            Self::Color => 'C',
+            Self::Item => 'D',
+            Self::False => 'F',
+            Self::Group => 'G',
+            Self::Null => 'N',
+            Self::EmptyString => 'S',
+            Self::True => 'T',
+        }
+    }
+
+    pub fn spwn_code(&self) -> &'static str {
+        match self {
+            Self::EmptyArray => "[]",
+            Self::Block => "?b",
+            Self::Color => "?c",
+            Self::Item => "?i",
+            Self::False => "false",
+            Self::Group => "?g",
+            Self::Null => "null",
+            Self::EmptyString => "\"\"",
+            Self::True => "true",
+        }
+    }
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: SimpleSpan) -> Self {
+        Self { kind, span }
+    }
+
+    /// Whether both expressions are the same apart from their spans.
+    pub fn same_as(&self, other: &Expr) -> bool {
+        let same_kind = match (&self.kind, &other.kind) {
+            (ExprKind::Number(a), ExprKind::Number(b))
+            | (ExprKind::String(a), ExprKind::String(b))
+            | (ExprKind::TypeIndicator(a), ExprKind::TypeIndicator(b))
+            | (ExprKind::Ident(a), ExprKind::Ident(b))
+            | (ExprKind::ShortMultiplication(a, _), ExprKind::ShortMultiplication(b, _))
+            | (ExprKind::MemberAccess { name: a, .. }, ExprKind::MemberAccess { name: b, .. }) => a == b,
+            (ExprKind::Constant(a), ExprKind::Constant(b)) => a == b,
+            (ExprKind::LoopVariable(a), ExprKind::LoopVariable(b)) => a == b,
+            (ExprKind::Macro { params: a, .. }, ExprKind::Macro { params: b, .. }) => a == b,
+            (a, b) => std::mem::discriminant(a) == std::mem::discriminant(b),
+        };
+
+        fn children(expr: &Expr) -> Vec<&Expr> {
+            let mut children = Vec::new();
+            expr.for_each_child(|child| children.push(child));
+            children
+        }
+
+        same_kind && same_stmts(&children(self), &children(other))
+    }
+
+    /// Calls `f` on every direct child expression.
+    pub fn for_each_child<'a>(&'a self, mut f: impl FnMut(&'a Expr)) {
+        match &self.kind {
+            ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::TypeIndicator(_)
+            | ExprKind::Constant(_)
+            | ExprKind::LoopVariable(_)
+            | ExprKind::Ident(_) => (),
+
+            ExprKind::ShortMultiplication(_, expr)
+            | ExprKind::Invert(expr)
+            | ExprKind::Print(expr)
+            | ExprKind::OnTouch(expr)
+            | ExprKind::MemberAccess { value: expr, .. }
+            | ExprKind::MacroCallNoArgs(expr) => f(expr),
+
+            ExprKind::Block(stmts)
+            | ExprKind::TriggerFunction(stmts)
+            | ExprKind::Macro { body: stmts, .. }
+            | ExprKind::Loop(stmts) => stmts.iter().for_each(f),
+
+            ExprKind::Assign { target, value } => {
+                f(target);
+                f(value);
+            }
+        }
+    }
+
+    /// Calls `f` on every direct child expression, allowing them to be modified.
+    pub fn for_each_child_mut(&mut self, mut f: impl FnMut(&mut Expr)) {
+        match &mut self.kind {
+            ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::TypeIndicator(_)
+            | ExprKind::Constant(_)
+            | ExprKind::LoopVariable(_)
+            | ExprKind::Ident(_) => (),
+
+            ExprKind::ShortMultiplication(_, expr)
+            | ExprKind::Invert(expr)
+            | ExprKind::Print(expr)
+            | ExprKind::OnTouch(expr)
+            | ExprKind::MemberAccess { value: expr, .. }
+            | ExprKind::MacroCallNoArgs(expr) => f(expr),
+
+            ExprKind::Block(stmts)
+            | ExprKind::TriggerFunction(stmts)
+            | ExprKind::Macro { body: stmts, .. }
+            | ExprKind::Loop(stmts) => stmts.iter_mut().for_each(f),
+
+            ExprKind::Assign { target, value } => {
+                f(target);
+                f(value);
+            }
+        }
+    }
+}
+
+/// Whether both lists contain the same expressions apart from their spans.
+pub fn same_stmts<E: std::borrow::Borrow<Expr>>(a: &[E], b: &[E]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.borrow().same_as(b.borrow()))
+}
@@ -0,0 +1,6 @@
+//! Language server for SCGT, communicating over stdin and stdout.
+
+#[tokio::main]
+async fn main() {
+    scgt::serve_stdio().await;
+}
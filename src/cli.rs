@@ -0,0 +1,546 @@
+//! Command line interface of the `spwncodegolftest` binary.
+
+use std::{collections::{BTreeMap, BTreeSet}, fs, io::{self, Write}, path::{Path, PathBuf}, process};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use crate::config::{self, Config};
+use crate::style::{BraceStyle, Comments, Indent, Style};
+use crate::stats::Stats;
+use crate::target::Target;
+use crate::util::{self, diagnostics::{Diagnostic, Severity}};
+use crate::util::helpers::{self, HelperImpls};
+use crate::util::source_map::SourceMap;
+use crate::warnings::{Lint, LintLevels};
+use crate::ast::Expr;
+use crate::codegen::{Generated, Output};
+use crate::{codegen, explain, format, from_spwn, golf, map_error, parser, HelpersMode, Options, HELPERS_MODULE};
+
+#[derive(Parser, Debug)]
+#[command(author = "kr8gz", verbatim_doc_comment, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+/// A golfing language that compiles to SPWN code.
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The paths to the SCGT files to be compiled.
+    ///
+    /// A single file is compiled to output.spwn, multiple files to a `.spwn` file next to each of them.
+    // #[arg(forbid_empty_values = true)]
+    #[arg(required_unless_present = "emit_helpers")]
+    files: Vec<String>,
+
+    #[arg(short, long, value_name = "SPACES")]
+    /// Indentation size for generated SPWN code [default: 4]
+    indent_size: Option<usize>,
+
+    #[arg(long, conflicts_with = "indent_size")]
+    /// Indent generated SPWN code with tabs instead of spaces.
+    tabs: bool,
+
+    #[arg(long, value_name = "LINES")]
+    /// Number of empty lines between top-level statements [default: 1]
+    blank_lines: Option<usize>,
+
+    #[arg(long, value_enum, value_name = "STYLE")]
+    /// Placement of opening braces [default: same-line]
+    brace_style: Option<BraceStyle>,
+
+    #[arg(long)]
+    /// Don't end generated files with a newline.
+    no_trailing_newline: bool,
+
+    #[arg(long, value_enum, value_name = "MODE")]
+    /// Comments referring generated code back to the source [default: every-statement]
+    comments: Option<Comments>,
+
+    #[arg(long, value_enum, value_name = "VERSION")]
+    /// SPWN version to generate code for [default: 0.8]
+    ///
+    /// Can also be set with the `target` key in scgt.toml.
+    target: Option<Target>,
+
+    #[arg(long, value_name = "DIR")]
+    /// Directory with helper implementations to use instead of the built-in ones.
+    ///
+    /// Helpers without a file in this directory keep their built-in implementation.
+    /// Can also be set with the `helpers-dir` key in scgt.toml.
+    helpers_dir: Option<PathBuf>,
+
+    #[arg(long, value_parser = config::parse_prefix)]
+    /// Prefix of generated identifiers, `_scgt_` by default.
+    ///
+    /// Can also be set with the `prefix` key in scgt.toml.
+    prefix: Option<String>,
+
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = HelpersMode::Inline)]
+    /// Where the generated code gets its helper functions from.
+    helpers_mode: HelpersMode,
+
+    #[arg(long)]
+    /// Also write a JSON source map next to each generated file, named `<output>.map`.
+    source_map: bool,
+
+    #[arg(long)]
+    /// Print the size of each file and its generated code to stderr, with a breakdown by statement.
+    stats: bool,
+
+    #[arg(long, value_name = "DIR", conflicts_with = "files")]
+    /// Write the built-in helper implementations for the target to a directory and exit.
+    emit_helpers: Option<PathBuf>,
+
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+    /// Output format for errors and warnings.
+    message_format: MessageFormat,
+
+    #[arg(short = 'W', long, value_enum, value_name = "LINT", value_delimiter = ',')]
+    /// Report warnings for the given lints.
+    warn: Vec<Lint>,
+
+    #[arg(short = 'A', long, value_enum, value_name = "LINT", value_delimiter = ',')]
+    /// Silence warnings for the given lints.
+    allow: Vec<Lint>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a breakdown of every construct in an SCGT file with comments explaining them.
+    Explain {
+        /// The path to the SCGT file to be explained.
+        file: String,
+
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+        /// Output format for errors.
+        message_format: MessageFormat,
+    },
+
+    /// Print SCGT code with one statement per line and every closing written out.
+    Fmt {
+        /// The path to the SCGT file to be formatted.
+        file: String,
+
+        #[arg(long)]
+        /// Leave out every closing, space and newline that isn't needed instead.
+        golf: bool,
+
+        #[arg(short, long)]
+        /// Overwrite the file instead of printing the result.
+        write: bool,
+
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+        /// Output format for errors.
+        message_format: MessageFormat,
+    },
+
+    /// Search for shorter SCGT code that generates the same SPWN code.
+    ///
+    /// Prints the shortest code found and reports how many bytes each rewrite saved on stderr.
+    Golf {
+        /// The path to the SCGT file to be golfed.
+        file: String,
+
+        #[arg(short, long)]
+        /// Overwrite the file instead of printing the result.
+        write: bool,
+
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+        /// Output format for errors.
+        message_format: MessageFormat,
+    },
+
+    /// Convert SPWN code to SCGT code.
+    ///
+    /// Prints the shortest code found. Parts that can't be converted are written as `//` comments
+    /// and reported as warnings, and have to be ported by hand.
+    FromSpwn {
+        /// The path to the SPWN file to be converted.
+        file: String,
+
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+        /// Output format for warnings, which are always written to stderr.
+        message_format: MessageFormat,
+    },
+
+    /// Point an error from the SPWN compiler at the SCGT code that caused it.
+    ///
+    /// Reads the error from stdin and needs the source map written by `--source-map`.
+    MapError {
+        #[arg(long, value_name = "FILE")]
+        /// Source map to use instead of the one next to the file named in the error.
+        map: Option<PathBuf>,
+
+        #[arg(long, value_enum, value_name = "FORMAT", default_value_t = MessageFormat::Human)]
+        /// Output format for the error.
+        message_format: MessageFormat,
+    },
+}
+
+impl Args {
+    /// Output style from the arguments, falling back to the configuration file.
+    fn style(&self, config: &Config) -> Style {
+        let config = config.style.style();
+
+        let indent = match (self.indent_size, self.tabs) {
+            (Some(n), _) => Indent::Spaces(n),
+            (_, true) => Indent::Tabs,
+            _ => config.indent,
+        };
+
+        Style {
+            indent,
+            blank_lines: self.blank_lines.unwrap_or(config.blank_lines),
+            brace_style: self.brace_style.unwrap_or(config.brace_style),
+            trailing_newline: !self.no_trailing_newline && config.trailing_newline,
+            comments: self.comments.unwrap_or(config.comments),
+        }
+    }
+
+    /// Applies `-W` and `-A` flags in the order they were given.
+    fn lint_levels(&self, matches: &ArgMatches) -> LintLevels {
+        let mut flags = Vec::new();
+
+        for (id, lints, warn) in [("warn", &self.warn, true), ("allow", &self.allow, false)] {
+            if let Some(indices) = matches.indices_of(id) {
+                flags.extend(indices.zip(lints).map(|(i, &lint)| (i, lint, warn)));
+            }
+        }
+
+        flags.sort_by_key(|&(i, ..)| i);
+
+        let mut levels = LintLevels::default();
+        for (_, lint, warn) in flags {
+            if warn {
+                levels.warn(lint);
+            } else {
+                levels.allow(lint);
+            }
+        }
+        levels
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum MessageFormat {
+    /// Rendered reports on stderr.
+    Human,
+    /// One JSON object per line on stdout, or on stderr when stdout holds converted code.
+    Json,
+}
+
+pub fn run() {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let config = Config::load().unwrap_or_else(|err| util::errors::simple(err));
+
+    match &args.command {
+        Some(Command::Explain { file, message_format }) => {
+            let (code, ast) = parse_file(file, *message_format);
+            println!("{}", explain::explain(&ast, &code));
+            return;
+        }
+        Some(Command::Fmt { file, golf, write, message_format }) => {
+            let (_, ast) = parse_file(file, *message_format);
+            let formatted = if *golf { format::golf(&ast) } else { format::format(&ast) }
+                .unwrap_or_else(|err| util::errors::simple(err));
+
+            if *write {
+                fs::write(file, formatted).unwrap_or_else(|err| util::errors::simple(err));
+            } else {
+                print!("{formatted}");
+            }
+            return;
+        }
+        Some(Command::Golf { file, write, message_format }) => {
+            let (code, ast) = parse_file(file, *message_format);
+            let golfed = golf::golf(&ast, &code).unwrap_or_else(|err| util::errors::simple(err));
+
+            for (rewrite, saved) in &golfed.savings {
+                eprintln!("{}: {saved} bytes", util::value_name(rewrite));
+            }
+            eprintln!("{} -> {} bytes", code.len(), golfed.code.len());
+
+            if *write {
+                fs::write(file, golfed.code).unwrap_or_else(|err| util::errors::simple(err));
+            } else {
+                println!("{}", golfed.code);
+            }
+            return;
+        }
+        Some(Command::FromSpwn { file, message_format }) => return from_spwn(file, *message_format),
+        Some(Command::MapError { map, message_format }) => return map_spwn_error(map.as_deref(), *message_format),
+        None => (),
+    }
+
+    let target = args.target.or(config.target).unwrap_or(Target::V0_8);
+
+    if let Some(dir) = &args.emit_helpers {
+        let written = helpers::emit_builtin(target, dir).unwrap_or_else(|err| util::errors::simple(err));
+        for path in written {
+            println!("{path}");
+        }
+        return;
+    }
+
+    let impls = match args.helpers_dir.as_ref().or(config.helpers_dir.as_ref()) {
+        Some(dir) => HelperImpls::load(target, dir).unwrap_or_else(|err| util::errors::simple(err)),
+        None => HelperImpls::builtin(target),
+    };
+
+    let prefix = match &args.prefix {
+        Some(prefix) => prefix.clone(),
+        None => config.prefix().unwrap_or_else(|err| util::errors::simple(err)),
+    };
+
+    let options = Options {
+        style: args.style(&config),
+        target,
+        prefix,
+        helpers_mode: args.helpers_mode,
+        lints: args.lint_levels(&matches),
+    };
+
+    let mut output_paths = BTreeSet::new();
+    for file in &args.files {
+        let path = output_path(file, args.files.len());
+        if path == *file {
+            util::errors::simple(format!("{file} would be overwritten by its own output"));
+        }
+        if !output_paths.insert(path.clone()) {
+            util::errors::simple(format!("Multiple files would be compiled to {path}"));
+        }
+    }
+
+    if let HelpersMode::Import = args.helpers_mode {
+        for module in output_paths.iter().map(|path| helpers_module_path(path)).collect::<BTreeSet<_>>() {
+            if output_paths.contains(&module) || args.files.contains(&module) {
+                util::errors::simple(format!("{module} would be overwritten by the helpers module"));
+            }
+        }
+    }
+
+    let mut generated = Vec::new();
+    let mut failed = false;
+
+    for file in &args.files {
+        let file_source = fs::read_to_string(file).unwrap_or_else(|err| util::errors::simple(err));
+        let code = file_source.replace("\r\n", "\n");
+
+        if code == "SCGT" {
+            let _ = open::that("https://github.com/kr8gz/scgt/");
+            fs::write(output_path(file, args.files.len()), ":)").unwrap_or_else(|err| util::errors::simple(err));
+            continue;
+        }
+
+        let (output, diagnostics) = generate_file(&file_source, &options);
+
+        for diagnostic in &diagnostics {
+            match args.message_format {
+                MessageFormat::Human => diagnostic.eprint(file, &file_source),
+                MessageFormat::Json => println!("{}", diagnostic.to_json(file, &file_source)),
+            }
+        }
+
+        if let Some(output) = output {
+            generated.push((file, file_source, output_path(file, args.files.len()), output));
+        }
+
+        failed |= diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error);
+    }
+
+    let write = |path: &str, contents: String| {
+        fs::write(path, contents).unwrap_or_else(|err| util::errors::simple(err))
+    };
+
+    if let HelpersMode::Import = args.helpers_mode {
+        let mut outputs_by_module = BTreeMap::<_, Vec<_>>::new();
+        for (_, _, path, output) in &generated {
+            outputs_by_module.entry(helpers_module_path(path)).or_default().push(output);
+        }
+
+        for (module, outputs) in outputs_by_module {
+            if outputs.iter().any(|output| !output.helpers.is_empty()) {
+                write(&module, codegen::helpers_module(outputs, &impls, &options.codegen()));
+            }
+        }
+    }
+
+    for (file, file_source, path, generated) in &generated {
+        let output = match args.helpers_mode {
+            HelpersMode::Inline => generated.with_helpers(&impls),
+            HelpersMode::Import => generated.with_import(HELPERS_MODULE),
+        }
+        .unwrap_or_else(|err| util::errors::simple(err));
+
+        if args.source_map {
+            write(&format!("{path}.map"), file_source_map(path, file, file_source, &output).to_json());
+        }
+        if args.stats {
+            eprintln!("{}", Stats::new(file_source, &output.code, generated.helpers.len()).report(file, path));
+        }
+        write(path, output.code);
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Generates code for a file as it was read, with the spans of diagnostics referring to the file rather than to
+/// the code with normalized newlines.
+fn generate_file(file_source: &str, options: &Options) -> (Option<Generated>, Vec<Diagnostic>) {
+    let (output, mut diagnostics) = crate::generate(&file_source.replace("\r\n", "\n"), options);
+    crate::restore_diagnostic_spans(file_source, &mut diagnostics);
+    (output, diagnostics)
+}
+
+/// Source map of a generated file, with source spans referring to the file it was generated from as it was read.
+fn file_source_map(path: &str, file: &str, file_source: &str, output: &Output) -> SourceMap {
+    let mut mappings = output.mappings.clone();
+    crate::restore_mapping_spans(file_source, &mut mappings);
+    SourceMap::new(path, file, &output.code, &mappings)
+}
+
+/// Reads and parses an SCGT file, exiting if it contains syntax errors.
+fn parse_file(file: &str, message_format: MessageFormat) -> (String, Vec<Expr>) {
+    let file_source = fs::read_to_string(file).unwrap_or_else(|err| util::errors::simple(err));
+    let code = file_source.replace("\r\n", "\n");
+
+    let (ast, errors) = parser::parse(&code).into_output_errors();
+    let mut diagnostics = errors.iter().map(Diagnostic::from_rich).collect::<Vec<_>>();
+    crate::restore_diagnostic_spans(&file_source, &mut diagnostics);
+
+    for diagnostic in &diagnostics {
+        match message_format {
+            MessageFormat::Human => diagnostic.eprint(file, &file_source),
+            MessageFormat::Json => println!("{}", diagnostic.to_json(file, &file_source)),
+        }
+    }
+
+    match ast {
+        Some(ast) if errors.is_empty() => (code, ast),
+        _ => process::exit(1),
+    }
+}
+
+fn from_spwn(file: &str, message_format: MessageFormat) {
+    let file_source = fs::read_to_string(file).unwrap_or_else(|err| util::errors::simple(err));
+    convert_spwn(file, &file_source, message_format, &mut io::stdout(), &mut io::stderr())
+        .unwrap_or_else(|err| util::errors::simple(err));
+}
+
+/// Writes the converted code to `out` and the warnings to `err`, so that JSON warnings don't end up
+/// in the code.
+fn convert_spwn(
+    file: &str,
+    file_source: &str,
+    message_format: MessageFormat,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> Result<(), String> {
+    let mut converted = from_spwn::from_spwn(&file_source.replace("\r\n", "\n"))?;
+    crate::restore_diagnostic_spans(file_source, &mut converted.warnings);
+
+    for warning in &converted.warnings {
+        match message_format {
+            MessageFormat::Human => warning.eprint(file, file_source),
+            MessageFormat::Json => writeln!(err, "{}", warning.to_json(file, file_source)).map_err(|err| err.to_string())?,
+        }
+    }
+
+    writeln!(out, "{}", converted.code).map_err(|err| err.to_string())
+}
+
+fn map_spwn_error(map: Option<&Path>, message_format: MessageFormat) {
+    let output = io::read_to_string(io::stdin()).unwrap_or_else(|err| util::errors::simple(err));
+    let Some(error) = map_error::parse(&output) else {
+        util::errors::simple("Could not find a location in a .spwn file in the error")
+    };
+
+    let map_path = map.map_or_else(|| PathBuf::from(format!("{}.map", error.file)), Path::to_path_buf);
+    let map = fs::read_to_string(&map_path)
+        .map_err(|err| format!("Could not read source map {}: {err}", map_path.display()))
+        .and_then(|json| SourceMap::from_json(&json))
+        .unwrap_or_else(|err| util::errors::simple(err));
+
+    let Some(span) = map.source_span(error.line, error.column) else {
+        util::errors::simple(format!(
+            "Line {} of {} was not generated from SCGT code, the error is probably in a helper function",
+            error.line, map.file,
+        ))
+    };
+
+    // source maps refer to the file as it is, so newlines aren't normalized
+    let source = fs::read_to_string(&map.source).unwrap_or_else(|err| util::errors::simple(err));
+
+    let diagnostic = Diagnostic {
+        severity: Severity::Error,
+        message: format!("SPWN error: {}", error.message),
+        span: span.clone(),
+        labels: vec![(span, format!("generated line {}, column {} of {}", error.line, error.column, map.file))],
+        notes: Vec::new(),
+    };
+
+    match message_format {
+        MessageFormat::Human => diagnostic.eprint(&map.source, &source),
+        MessageFormat::Json => println!("{}", diagnostic.to_json(&map.source, &source)),
+    }
+}
+
+/// Path of the helpers module imported by a generated file.
+///
+/// The module is imported by a relative path, so every directory with generated files gets its own.
+fn helpers_module_path(output: &str) -> String {
+    let dir = Path::new(output).parent().unwrap_or(Path::new(""));
+    dir.join(HELPERS_MODULE).to_string_lossy().into_owned()
+}
+
+/// Path of the generated SPWN file for an input file.
+fn output_path(file: &str, file_count: usize) -> String {
+    if file_count == 1 {
+        String::from("output.spwn")
+    } else {
+        Path::new(file).with_extension("spwn").to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crlf_spans() {
+        let (_, diagnostics) = generate_file("a!2;\r\n\r\n$b", &Options::default());
+        let spans = diagnostics.iter().map(|diagnostic| diagnostic.span.clone()).collect::<Vec<_>>();
+        assert_eq!(spans, [0..1, 9..10]);
+    }
+
+    #[test]
+    fn helpers_module_paths() {
+        assert_eq!(helpers_module_path("output.spwn"), HELPERS_MODULE);
+        assert_eq!(helpers_module_path(&output_path("a/scgt_helpers.scgt", 2)), format!("a/{HELPERS_MODULE}"));
+        assert_eq!(output_path("a/scgt_helpers.scgt", 2), format!("a/{HELPERS_MODULE}"));
+    }
+
+    #[test]
+    fn crlf_source_map() {
+        let file_source = "a!2;\r\n\r\n$a";
+        let (generated, _) = generate_file(file_source, &Options::default());
+        let output = generated.unwrap().with_helpers(&HelperImpls::builtin(Target::V0_8)).unwrap();
+
+        let map = file_source_map("output.spwn", "in.scgt", file_source, &output);
+        assert_eq!(map.source_span(5, 1), Some(8..10));
+    }
+
+    #[test]
+    fn from_spwn_streams() {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        convert_spwn("in.spwn", "$.print({b: 1})\r\n$.print(1)", MessageFormat::Json, &mut out, &mut err).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "// $.print({b: 1})\n1\n");
+        let err = String::from_utf8(err).unwrap();
+        let warnings = err.lines().collect::<Vec<_>>();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(r#"{"file":"in.spwn","severity":"warning","message":"Dictionaries"#));
+        assert!(warnings[0].contains(r#""end_line":1"#));
+    }
+}
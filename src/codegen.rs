@@ -0,0 +1,952 @@
+// throughout this file '#' will be used as a placeholder for generated code to be inserted
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chumsky::span::SimpleSpan;
+
+use crate::ast::*;
+use crate::style::{Comments, Style};
+use crate::target::Target;
+use crate::types::{self, Type, Types};
+use crate::util::helpers::*;
+use crate::util::diagnostics::line_column;
+use crate::util::names::Names;
+use crate::util::source_map::{self, Mapping};
+
+/// Settings for code generation.
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub style: Style,
+    pub target: Target,
+    /// Prefix of generated identifiers.
+    pub prefix: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            style: Style::default(),
+            target: Target::V0_8,
+            prefix: DEFAULT_PREFIX.to_string(),
+        }
+    }
+}
+
+struct State<'a> {
+    helpers: BTreeSet<HelperFunction>,
+    names: Names,
+    types: Types,
+    source: &'a str,
+    options: &'a Options,
+    /// Number of statements that the code being generated is nested in.
+    depth: usize,
+
+    /// Number of times each variable is used in the whole program.
+    total_uses: BTreeMap<String, usize>,
+    /// Uses of variables not yet declared, for each scope being generated.
+    scopes: Vec<BTreeMap<String, usize>>,
+    macro_params: Vec<Vec<String>>,
+}
+
+impl<'a> State<'a> {
+    fn new(stmts: &[Expr], source: &'a str, options: &'a Options) -> Self {
+        let mut user_names = BTreeSet::new();
+        stmts.iter().for_each(|stmt| collect_idents(stmt, &mut user_names));
+
+        let mut state = Self {
+            helpers: BTreeSet::new(),
+            names: Names::new(&options.prefix, user_names),
+            types: types::infer(stmts),
+            source,
+            options,
+            depth: 0,
+
+            total_uses: BTreeMap::new(),
+            scopes: Vec::new(),
+            macro_params: Vec::new(),
+        };
+
+        stmts.iter().for_each(|stmt| state.count_uses(stmt));
+        state
+    }
+
+    fn count_uses(&mut self, expr: &Expr) {
+        let name = match &expr.kind {
+            ExprKind::Ident(name) if !self.is_macro_param(name) => name.clone(),
+            ExprKind::LoopVariable(name) => self.loop_variable_name(*name),
+
+            ExprKind::Macro { params, body } => {
+                self.macro_params.push(params.clone());
+                body.iter().for_each(|stmt| self.count_uses(stmt));
+                self.macro_params.pop();
+                return;
+            }
+
+            _ => return expr.for_each_child(|child| self.count_uses(child)),
+        };
+
+        *self.total_uses.entry(name).or_default() += 1;
+    }
+
+    fn is_macro_param(&self, name: &str) -> bool {
+        self.macro_params.iter().flatten().any(|param| param == name)
+    }
+
+    fn loop_variable_name(&mut self, name: char) -> String {
+        self.names.stable(&format!("loop_{}", name.to_lowercase()))
+    }
+
+    /// Records a use of a variable, which will be declared in the innermost scope containing all of its uses.
+    fn use_variable(&mut self, name: &str) {
+        if !self.is_macro_param(name) {
+            let scope = self.scopes.last_mut().expect("variables should only be used inside a scope");
+            *scope.entry(name.to_string()).or_default() += 1;
+        }
+    }
+
+    fn uses_in_scope(&self, name: &str) -> usize {
+        self.scopes.last().and_then(|scope| scope.get(name)).copied().unwrap_or_default()
+    }
+
+    fn add_helper(&mut self, helper: HelperFunction) -> String {
+        self.helpers.insert(helper);
+        self.names.stable(helper.name())
+    }
+
+    fn get_indent(&self) -> String {
+        self.options.style.indent()
+    }
+}
+
+pub(crate) enum PrintBehavior {
+    Implicit,
+    Explicit,
+}
+
+impl Expr {
+    /// Whether the value of the expression is printed when it is used as a statement.
+    pub(crate) fn print_behavior(&self) -> PrintBehavior {
+        match self.kind {
+            ExprKind::Print(_)
+            | ExprKind::OnTouch(_)
+            | ExprKind::Loop(_)
+            | ExprKind::Assign { .. }
+            | ExprKind::Ident(_)
+            | ExprKind::MacroCallNoArgs(_) => PrintBehavior::Explicit,
+
+            _ => PrintBehavior::Implicit,
+        }
+    }
+}
+
+fn collect_idents(expr: &Expr, idents: &mut BTreeSet<String>) {
+    match &expr.kind {
+        ExprKind::Ident(name) => { idents.insert(name.clone()); }
+        ExprKind::Macro { params, .. } => idents.extend(params.iter().cloned()),
+        _ => (),
+    }
+    expr.for_each_child(|child| collect_idents(child, idents));
+}
+
+/// Generated code along with the helpers it needs.
+pub struct Generated {
+    /// Code with statements marked for the source map.
+    code: String,
+    /// Helpers called by the code and their dependencies, along with the names they are called by in the code.
+    /// Every helper comes after its dependencies.
+    pub helpers: Vec<(HelperFunction, String)>,
+    /// Helpers called by the code itself rather than only by other helpers.
+    called: BTreeSet<HelperFunction>,
+    options: Options,
+}
+
+pub fn generate(stmts: &[Expr], source: &str, options: &Options) -> Generated {
+    let mut state = State::new(stmts, source, options);
+    let code = format_scope(stmts, &mut state, true);
+
+    let called = std::mem::take(&mut state.helpers);
+    let helpers = resolve_dependencies(called.iter().copied())
+        .into_iter()
+        .map(|helper| (helper, state.names.stable(helper.name())))
+        .collect();
+
+    Generated { code, helpers, called, options: options.clone() }
+}
+
+/// A complete generated file.
+pub struct Output {
+    pub code: String,
+    pub mappings: Vec<Mapping>,
+}
+
+impl Output {
+    fn new(marked: &str) -> Result<Self, String> {
+        let (code, mappings) = source_map::strip_markers(marked)?;
+        Ok(Self { code, mappings })
+    }
+}
+
+impl Generated {
+    /// The code with definitions of its helpers at the start.
+    pub fn with_helpers(&self, impls: &HelperImpls) -> Result<Output, String> {
+        let style = &self.options.style;
+        if self.helpers.is_empty() {
+            return Output::new(&style.end_file(&self.code));
+        }
+
+        Output::new(&style.end_file(&format!("{}{}", format_helpers(&self.helpers, impls, &self.options), self.code)))
+    }
+
+    /// The code importing its helpers from a module created by [`helpers_module`].
+    pub fn with_import(&self, module: &str) -> Result<Output, String> {
+        let imported = self.helpers
+            .iter()
+            .filter(|(helper, _)| self.called.contains(helper))
+            .map(|(helper, name)| match format!("{}{}", self.options.prefix, helper.name()) {
+                key if key == *name => key,
+                key => format!("{key}: {name}"),
+            })
+            .collect::<Vec<_>>();
+
+        let style = &self.options.style;
+        if imported.is_empty() {
+            return Output::new(&style.end_file(&self.code));
+        }
+
+        let import = self.options.target.constant_declaration(
+            &format!("{{{}}}", imported.join(", ")),
+            &format!("import {}", format_string(module)),
+        );
+        Output::new(&style.end_file(&format!("{import}{}{}", style.separator(), self.code)))
+    }
+}
+
+/// Module that defines and exports all helpers used by any of the given generated files.
+pub fn helpers_module<'a>(files: impl IntoIterator<Item = &'a Generated>, impls: &HelperImpls, options: &Options) -> String {
+    let used = files
+        .into_iter()
+        .flat_map(|file| file.called.iter().copied())
+        .collect::<BTreeSet<_>>();
+
+    // nothing else is defined in the module, so no names need to be avoided
+    let helpers = resolve_dependencies(used.iter().copied())
+        .into_iter()
+        .map(|helper| (helper, format!("{}{}", options.prefix, helper.name())))
+        .collect::<Vec<_>>();
+
+    let indent = options.style.indent();
+    let exports = used
+        .iter()
+        .map(|helper| format!("{indent}{0}{1}: {0}{1},\n", options.prefix, helper.name()))
+        .collect::<String>();
+
+    options.style.end_file(&format!("{}return {{\n{exports}}}", format_helpers(&helpers, impls, options)))
+}
+
+fn format_helpers(helpers: &[(HelperFunction, String)], impls: &HelperImpls, options: &Options) -> String {
+    let separator = options.style.separator();
+    let name_of = |helper: HelperFunction| {
+        helpers
+            .iter()
+            .find(|&&(h, _)| h == helper)
+            .map(|(_, name)| name.as_str())
+            .expect("dependencies should be resolved")
+    };
+
+    let definitions = helpers
+        .iter()
+        .map(|(helper, name)| {
+            let mut code = source_map::escape_markers(&options.style.reindent(impls.get(*helper)));
+            for &dep in helper.dependencies() {
+                code = rename_identifier(&code, &format!("{DEFAULT_PREFIX}{}", dep.name()), name_of(dep));
+            }
+            format!("{name} = {code}{separator}")
+        })
+        .collect::<String>();
+
+    format!("// Automatically generated helper functions\n{definitions}")
+}
+
+/// Replaces every identifier `from` in SPWN `code` with `to`, leaving strings and comments untouched.
+fn rename_identifier(code: &str, from: &str, to: &str) -> String {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut renamed = String::with_capacity(code.len());
+    let mut rest = code;
+
+    while let Some(c) = rest.chars().next() {
+        let len = if is_ident_char(c) {
+            let len = rest.find(|c| !is_ident_char(c)).unwrap_or(rest.len());
+            if &rest[..len] == from {
+                renamed.push_str(to);
+                rest = &rest[len..];
+                continue;
+            }
+            len
+        } else if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            rest.find("*/").map_or(rest.len(), |end| end + 2)
+        } else if c == '"' || c == '\'' {
+            let mut chars = rest.char_indices().skip(1);
+            let mut end = rest.len();
+            while let Some((i, ch)) = chars.next() {
+                match ch {
+                    '\\' => { chars.next(); }
+                    _ if ch == c => { end = i + 1; break; }
+                    _ => (),
+                }
+            }
+            end
+        } else {
+            c.len_utf8()
+        };
+
+        renamed.push_str(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    renamed
+}
+
+/// Wraps code in parentheses unless it is a single identifier, literal or call.
+fn operand(code: String) -> String {
+    let is_simple = |code: &str| {
+        !code.is_empty() && code.chars().all(|c| c.is_alphanumeric() || "_.@?".contains(c))
+    };
+
+    let is_string = code.len() >= 2
+        && code.starts_with('"')
+        && code.ends_with('"')
+        && code[1..code.len() - 1].replace("\\\\", "").replace("\\\"", "").find('"').is_none();
+
+    let is_call = code.find('(').is_some_and(|open| {
+        let mut depth = 0;
+        let close = code[open..]
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => (),
+                }
+                depth == 0
+            })
+            .map(|(i, _)| open + i);
+
+        is_simple(&code[..open]) && close == Some(code.len() - 1)
+    });
+
+    if is_simple(&code) || is_string || is_call {
+        code
+    } else {
+        format!("({code})")
+    }
+}
+
+/// Code for an expression whose value is used.
+fn expr_code(expr: &Expr, state: &mut State) -> String {
+    match &expr.kind {
+        ExprKind::Number(n) => n.clone(),
+        ExprKind::String(s) => format_string(s),
+        ExprKind::TypeIndicator(name) => format!("@{name}"),
+        ExprKind::Constant(c) => c.spwn_code().to_string(),
+
+        ExprKind::LoopVariable(name) => {
+            let name = state.loop_variable_name(*name);
+            state.use_variable(&name);
+            name
+        }
+
+        ExprKind::Ident(name) => {
+            state.use_variable(name);
+            match state.types.of(expr) {
+                Some(t) if !t.shared_on_read() => name.clone(),
+                _ if !state.options.target.has_clone_bug() => name.clone(),
+                _ => {
+                    let helper = state.add_helper(HelperFunction::Get);
+                    format!("{helper}({name})")
+                }
+            }
+        }
+
+        ExprKind::ShortMultiplication(n, expr) => {
+            let code = expr_code(expr, state);
+            match state.types.of(expr) {
+                Some(Type::Array | Type::Number | Type::String) => format!("{n} * {}", operand(code)),
+                Some(t @ (Type::Group | Type::Color | Type::Item | Type::Block)) => {
+                    format!("{}({n} * @number({code}))", t.spwn_name())
+                }
+                Some(Type::Bool) => format!("{n} * @number({code})"),
+                Some(_) => code,
+                None => {
+                    let helper = state.add_helper(HelperFunction::Mul);
+                    format!("{helper}({n}, {code})")
+                }
+            }
+        }
+
+        ExprKind::Invert(expr) => {
+            let code = expr_code(expr, state);
+            match state.types.of(expr) {
+                Some(Type::Number) => format!("-{}", operand(code)),
+                Some(Type::Bool) => format!("!{}", operand(code)),
+                Some(Type::String | Type::Array) => format!("{}.reverse()", operand(code)),
+                Some(_) => code,
+                None => {
+                    let helper = state.add_helper(HelperFunction::Invert);
+                    format!("{helper}({code})")
+                }
+            }
+        }
+
+        ExprKind::Print(expr) => {
+            let code = expr_code(expr, state);
+            let helper = state.add_helper(HelperFunction::Print);
+            format!("{helper}({code})")
+        }
+
+        ExprKind::OnTouch(expr) => format!("on(touch(), {})", expr_code(expr, state)),
+
+        ExprKind::Block(stmts) => match format_value_scope(stmts, state) {
+            ScopeValue::Expr(code) => code,
+            ScopeValue::Body(code) => wrap_with_block(code, false, state),
+        },
+
+        ExprKind::TriggerFunction(stmts) => {
+            // TODO check back here when `-> return`
+            let code = format_scope(stmts, state, false);
+            state.options.target.trigger_function(&code)
+        }
+
+        ExprKind::Macro { params, body } => {
+            state.macro_params.push(params.clone());
+            let code = format_value_scope(body, state);
+            state.macro_params.pop();
+
+            let params = params.join(", ");
+            match code {
+                ScopeValue::Expr(code) => format!("({params}) => {code}"),
+                ScopeValue::Body(code) => state.options.style.block(&format!("({params})"), &code),
+            }
+        }
+
+        ExprKind::Loop(stmts) => format_loop_expr("while true", stmts, state),
+
+        ExprKind::Assign { target, value } => {
+            let (target, value) = format_assign(target, value, state);
+            let helper = state.add_helper(HelperFunction::Set);
+            format!("{helper}({target}, {value})")
+        }
+
+        ExprKind::MemberAccess { value, name } => format!("{}.{name}", place_code(value, state)),
+
+        ExprKind::MacroCallNoArgs(value) => {
+            let code = place_code(value, state);
+            match state.types.of(value) {
+                Some(Type::Macro) => format!("{}()", operand(code)),
+                Some(Type::Number) => state.options.target.call_in_place(&format!("() {{ @group({code})! }}")),
+                Some(Type::Group | Type::TriggerFunction) => state.options.target.call_in_place(&format!("() {{ {}! }}", operand(code))),
+                Some(_) => code,
+                None => {
+                    let helper = state.add_helper(HelperFunction::Call);
+                    format!("{helper}({code})")
+                }
+            }
+        }
+    }
+}
+
+/// Code for an expression at statement level whose value is discarded.
+fn stmt_code(expr: &Expr, state: &mut State) -> String {
+    match &expr.kind {
+        ExprKind::Print(expr) => format!("$.print({})", expr_code(expr, state)),
+
+        ExprKind::Loop(stmts) => format_loop_stmt("while true", stmts, state),
+
+        ExprKind::Assign { target, value } => {
+            let (target, value) = format_assign(target, value, state);
+            format!("{target} = {value}")
+        }
+
+        // the value of the last statement is not returned, so it doesn't need to be kept either
+        ExprKind::MacroCallNoArgs(value) => match &value.kind {
+            ExprKind::Macro { params, body } if params.is_empty() => {
+                // a value at the end would be returned by the macro, so it isn't printed
+                let discarded = body.last().is_some_and(|last| matches!(last.print_behavior(), PrintBehavior::Implicit));
+                let code = format_scope_with(body, state, false, discarded.then_some("#"));
+                wrap_with_block(code, false, state)
+            }
+            _ => place_code(expr, state),
+        },
+
+        _ => place_code(expr, state),
+    }
+}
+
+/// Code for an expression that is being accessed, called or assigned to.
+fn place_code(expr: &Expr, state: &mut State) -> String {
+    match &expr.kind {
+        ExprKind::Ident(name) => {
+            state.use_variable(name);
+            name.clone()
+        }
+        _ => expr_code(expr, state),
+    }
+}
+
+fn format_string(s: &str) -> String {
+    let escaped = s
+        .chars()
+        .map(|c| match c {
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            '"' => "\\\"".to_string(),
+            '\'' => "\\'".to_string(),
+            '\\' => "\\\\".to_string(),
+            c if source_map::is_marker(c) => source_map::escape_markers(&c.to_string()),
+            c => c.to_string(),
+        })
+        .collect::<String>();
+
+    format!("\"{escaped}\"")
+}
+
+/// A statement that has been generated but not yet indented.
+struct StmtCode {
+    span: SimpleSpan,
+    comment: String,
+    code: String,
+    /// Variable that this statement assigns before it is used anywhere else in the scope.
+    first_assignment: Option<String>,
+}
+
+/// Generates a block of statements that declares its own variables.
+///
+/// Loop bodies are not scopes, so that variables keep their values between iterations.
+fn format_scope(stmts: &[Expr], state: &mut State, global: bool) -> String {
+    format_scope_with(stmts, state, global, None)
+}
+
+fn format_scope_with(stmts: &[Expr], state: &mut State, global: bool, return_fmt: Option<&str>) -> String {
+    let stmts = stmts.iter().collect::<Vec<_>>();
+    let (stmts, declarations) = scope_stmt_codes(&stmts, state, return_fmt);
+    let code = join_stmts(stmts, state, global);
+
+    if declarations.is_empty() {
+        code
+    } else if global {
+        format!("// Initialize variables used\n{}{}{code}", declarations.join("\n"), state.options.style.separator())
+    } else {
+        indent_declarations(code, &declarations, state)
+    }
+}
+
+/// Code for a scope whose value is used.
+enum ScopeValue {
+    /// The scope consists of nothing but its value.
+    Expr(String),
+    /// The scope needs statements, the last of which returns the value.
+    Body(String),
+}
+
+/// Generates a scope whose value is the value of its last statement.
+fn format_value_scope(stmts: &[Expr], state: &mut State) -> ScopeValue {
+    // a block at the end of the scope can share its return
+    let mut merged = Vec::new();
+    let mut rest = stmts;
+    while let Some((last, init)) = rest.split_last() {
+        match &last.kind {
+            ExprKind::Block(inner) if !inner.is_empty() => {
+                merged.extend(init);
+                rest = inner;
+            }
+            _ => break,
+        }
+    }
+    merged.extend(rest);
+
+    let (mut stmts, declarations) = scope_stmt_codes(&merged, state, Some("#"));
+
+    match stmts.as_mut_slice() {
+        [] => ScopeValue::Expr(Constant::Null.spwn_code().to_string()),
+        [stmt] if declarations.is_empty() => ScopeValue::Expr(std::mem::take(&mut stmt.code)),
+        [.., last] => {
+            last.code = format!("return {}", last.code);
+            let code = join_stmts(stmts, state, false);
+            ScopeValue::Body(indent_declarations(code, &declarations, state))
+        }
+    }
+}
+
+/// Generates the statements of a scope along with the declarations of variables only used inside it.
+fn scope_stmt_codes(
+    stmts: &[&Expr],
+    state: &mut State,
+    return_fmt: Option<&str>,
+) -> (Vec<StmtCode>, Vec<String>) {
+    state.scopes.push(BTreeMap::new());
+    let mut stmts = stmt_codes(stmts, state, return_fmt, true);
+    let uses = state.scopes.pop().unwrap_or_default();
+
+    let keyword = state.options.target.declaration_keyword();
+    let mut declarations = Vec::new();
+
+    for (name, count) in uses {
+        if count == state.total_uses[&name] {
+            match stmts.iter_mut().find(|stmt| stmt.first_assignment.as_ref() == Some(&name)) {
+                Some(stmt) => stmt.code = format!("{keyword} {}", stmt.code),
+                None => declarations.push(format!("{keyword} {name} = null")),
+            }
+        } else if let Some(outer) = state.scopes.last_mut() {
+            *outer.entry(name).or_default() += count;
+        }
+    }
+
+    (stmts, declarations)
+}
+
+fn indent_declarations(code: String, declarations: &[String], state: &State) -> String {
+    let indent = state.get_indent();
+    declarations
+        .iter()
+        .rfold(code, |rest, declaration| format!("{indent}{declaration}\n{rest}"))
+}
+
+fn format_stmts(
+    stmts: &[Expr],
+    state: &mut State,
+    global: bool,
+    return_fmt: Option<&str>,
+) -> String {
+    let stmts = stmts.iter().collect::<Vec<_>>();
+    let stmts = stmt_codes(&stmts, state, return_fmt, false);
+    join_stmts(stmts, state, global)
+}
+
+fn stmt_codes(
+    stmts: &[&Expr],
+    state: &mut State,
+    return_fmt: Option<&str>,
+    scope: bool,
+) -> Vec<StmtCode> {
+    let last_index = stmts.len().saturating_sub(1);
+
+    stmts
+        .iter()
+        .enumerate()
+        .map(|(i, stmt)| {
+            let comment = source_comment(stmt, state);
+            let mut first_assignment = None;
+            state.depth += 1;
+
+            let code = match return_fmt {
+                Some(r) if i == last_index => r.replace('#', &expr_code(stmt, state)),
+                _ => match stmt.print_behavior() {
+                    PrintBehavior::Explicit => match &stmt.kind {
+                        ExprKind::Assign { target, .. } if scope => {
+                            let name = match &target.kind {
+                                ExprKind::Ident(name) if !state.is_macro_param(name) => Some(name),
+                                _ => None,
+                            };
+
+                            let unused = name.is_some_and(|name| state.uses_in_scope(name) == 0);
+                            let code = stmt_code(stmt, state);
+
+                            // the target itself is the only use so far
+                            if unused && name.is_some_and(|name| state.uses_in_scope(name) == 1) {
+                                first_assignment = name.cloned();
+                            }
+                            code
+                        }
+                        _ => stmt_code(stmt, state),
+                    },
+                    PrintBehavior::Implicit => format!("$.print({})", expr_code(stmt, state)),
+                }
+            };
+
+            state.depth -= 1;
+            StmtCode { span: stmt.span, comment, code, first_assignment }
+        })
+        .collect()
+}
+
+fn source_comment(stmt: &Expr, state: &State) -> String {
+    let source = || {
+        state.source[stmt.span.start..stmt.span.end]
+            .lines()
+            .map(|line| format!("// {}\n", source_map::escape_markers(line)))
+            .collect()
+    };
+
+    match state.options.style.comments {
+        Comments::None => String::new(),
+        Comments::TopLevelOnly if state.depth > 0 => String::new(),
+        Comments::TopLevelOnly | Comments::EveryStatement => source(),
+        Comments::Span => {
+            let (line, column) = line_column(state.source, stmt.span.start);
+            format!("// {line}:{column}\n")
+        }
+    }
+}
+
+fn join_stmts(stmts: Vec<StmtCode>, state: &State, global: bool) -> String {
+    let indent = if global { String::new() } else { state.get_indent() };
+
+    stmts
+        .into_iter()
+        .map(|stmt| {
+            format!("{}{}", stmt.comment, source_map::mark(&stmt.code, stmt.span.into_range()))
+                .lines()
+                .map(|line| format!("{indent}{line}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join(&if global { state.options.style.separator() } else { String::from("\n") })
+}
+
+fn wrap_with_block(mut code: String, indent: bool, state: &mut State) -> String {
+    if indent {
+        code = code
+            .lines()
+            .map(|line| format!("{}{line}", state.get_indent()))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    state.options.target.call_in_place(&state.options.style.block("()", &code))
+}
+
+fn format_loop_stmt(start: &str, stmts: &[Expr], state: &mut State) -> String {
+    if stmts.is_empty() {
+        state.options.style.empty_block(start)
+    } else {
+        let code = format_stmts(stmts, state, false, None);
+        state.options.style.block(start, &code)
+    }
+}
+
+fn format_loop_expr(start: &str, stmts: &[Expr], state: &mut State) -> String {
+    if stmts.is_empty() {
+        let code = state.options.style.empty_block(start);
+        wrap_with_block(code, true, state)
+    } else {
+        let arr_name = state.names.fresh("loop");
+
+        let code = format_stmts(stmts, state, false, Some(&format!("{arr_name}.push(#)")));
+        let keyword = state.options.target.declaration_keyword();
+        let code = format!("{keyword} {arr_name} = []\n{}\nreturn {arr_name}", state.options.style.block(start, &code));
+        wrap_with_block(code, true, state)
+    }
+}
+
+fn format_assign(target: &Expr, value: &Expr, state: &mut State) -> (String, String) {
+    (place_code(target, state), expr_code(value, state))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::style::{BraceStyle, Indent};
+
+    fn compile_with(code: &str, options: &Options) -> String {
+        let ast = crate::parser::parse_test_code(code);
+        generate(&ast, code, options).with_helpers(&HelperImpls::builtin(options.target)).unwrap().code
+    }
+
+    fn compile_for(code: &str, target: Target) -> String {
+        compile_with(code, &Options { target, ..Options::default() })
+    }
+
+    fn compile(code: &str) -> String {
+        compile_with(code, &Options::default())
+    }
+
+    #[test]
+    fn first_assignment_declares() {
+        let code = compile("a!3;$a");
+        assert!(code.contains("\nlet a = 3\n"));
+        assert!(!code.contains("let a = null"));
+    }
+
+    #[test]
+    fn innermost_scope() {
+        let code = compile("$(b!2;$b");
+        assert!(code.contains("    let b = 2\n"));
+        assert!(!code.contains("Initialize variables"));
+    }
+
+    #[test]
+    fn loops_are_not_scopes() {
+        let code = compile("L$a;a!1");
+        assert!(code.contains("// Initialize variables used\nlet a = null\n"));
+        assert!(!code.contains("let _scgt_loop_1 = null"));
+    }
+
+    #[test]
+    fn renamed_identifiers() {
+        assert_eq!(rename_identifier("_scgt_get(a) + _scgt_get_1 + __scgt_get", "_scgt_get", "g"), "g(a) + _scgt_get_1 + __scgt_get");
+        assert_eq!(
+            rename_identifier("\"_scgt_iter \\\" _scgt_iter\" // _scgt_iter\n_scgt_iter /* _scgt_iter */", "_scgt_iter", "i"),
+            "\"_scgt_iter \\\" _scgt_iter\" // _scgt_iter\ni /* _scgt_iter */",
+        );
+    }
+
+    #[test]
+    fn known_types() {
+        assert!(compile("!3").contains("$.print(-3)"));
+        assert!(compile("!!T").contains("$.print(!(!true))"));
+        assert!(compile("2'a").contains("$.print(2 * \"a\")"));
+        assert!(compile("!2'a").contains("$.print((2 * \"a\").reverse())"));
+        assert!(compile("a!3;\n$2a").contains("2 * a"));
+        assert!(!compile("!3").contains("_scgt_invert"));
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(operand(String::from("_scgt_get(a)")), "_scgt_get(a)");
+        assert_eq!(operand(String::from("\"a(b)\"")), "\"a(b)\"");
+        assert_eq!(operand(String::from("\"a\" + \"b\"")), "(\"a\" + \"b\")");
+        assert_eq!(operand(String::from("f(a) + g(b)")), "(f(a) + g(b))");
+        assert_eq!(operand(String::from("-3")), "(-3)");
+    }
+
+    #[test]
+    fn inlined_get_and_set() {
+        assert!(compile("a!3;\n$a").contains("$.print(a)"));
+        assert!(compile("a!L;\n$a").contains("$.print(_scgt_get(a))"));
+        assert!(compile("a!3;\na!'c;\n$a").contains("$.print(_scgt_get(a))"));
+
+        let code = compile("Ma!3;;M\n$a");
+        assert!(code.contains("    a = 3\n} ()"));
+        assert!(!code.contains("_scgt_set"));
+        assert!(compile("$Ma!3;;M").contains("return _scgt_set(a, 3)"));
+
+        let code = compile("M$2\n3;M");
+        assert!(code.contains("() {\n    // $2\n    $.print(2)\n    // 3\n    3\n} ()"));
+        assert!(!code.contains("$.print(3)"));
+    }
+
+    #[test]
+    fn unwrapped_blocks() {
+        assert!(compile("a!1;\n$(a").contains("\n$.print(a)\n"));
+        assert!(compile("$(").contains("$.print(null)"));
+        assert!(compile("fX$x").contains("let f = (x) => _scgt_print(_scgt_get(x))"));
+
+        let code = compile("$(b!2;(c!3;c");
+        assert_eq!(code.matches("() {").count(), 1);
+        assert!(code.contains("    let c = 3\n    // c\n    return c\n} ()"));
+    }
+
+    #[test]
+    fn targets() {
+        let code = compile_for("a!L3;;\n$a\n$(b!2;$b", Target::V0_9);
+        assert!(code.contains("\nmut a = (() {\n    mut _scgt_loop_1 = []\n"));
+        assert!(code.contains("\n})()\n"));
+        assert!(code.contains("$.print(a)"));
+        assert!(code.contains("    mut b = 2\n"));
+        assert!(!code.contains("let "));
+        assert!(!code.contains("_scgt_get"));
+
+        let code = compile_for("$2M\nb!}$1;\n$(b;M", Target::V0_9);
+        assert!(code.contains("$.print((() { @group(2)! })())"));
+        assert!(code.contains("mut b = !{\n"));
+        assert!(code.contains("(() { b! })()"));
+        assert!(compile_for("$2M", Target::V0_8).contains("$.print(() { @group(2)! } ())"));
+    }
+
+    #[test]
+    fn imported_helpers() {
+        let generate = |code: &str| {
+            let ast = crate::parser::parse_test_code(code);
+            generate(&ast, code, &Options::default())
+        };
+
+        let files = [generate("$(b!2;$b"), generate("__scgt_print!1;\n$(c!2;$c")];
+        assert!(files[0].with_import("h.spwn").unwrap().code.starts_with("let {_scgt_print} = import \"h.spwn\"\n\n"));
+        assert!(files[1].with_import("h.spwn").unwrap().code.starts_with("let {_scgt_print: _scgt_print_1} = import"));
+
+        let module = helpers_module(&files, &HelperImpls::builtin(Target::V0_8), &Options::default());
+        assert!(module.ends_with("return {\n    _scgt_print: _scgt_print,\n}\n"));
+
+        let options = Options { target: Target::V0_9, ..Options::default() };
+        let ast = crate::parser::parse_test_code("$(b!2;$b");
+        let output = super::generate(&ast, "$(b!2;$b", &options).with_import("h.spwn").unwrap();
+        assert!(output.code.starts_with("{_scgt_print} = import \"h.spwn\"\n\n"));
+        assert!(!module.contains("_scgt_get"));
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let options = Options { prefix: String::from("gen"), ..Options::default() };
+        let code = compile_with("$L$I;\n$_genprint\n$(b!2;$b", &options);
+        assert!(code.contains("genprint_1 = (v) {"));
+        assert!(code.contains("genloop_1.push(genprint_1(genloop_i))"));
+        assert!(!code.contains("_scgt_"));
+
+        let helpers = [(HelperFunction::Iter, String::from("geniter")), (HelperFunction::Mod, String::from("genmod"))];
+        let code = format_helpers(&helpers, &HelperImpls::builtin(Target::V0_8), &options);
+        assert!(code.contains("@array(geniter(result))"));
+        assert!(!code.contains("_scgt_"));
+    }
+
+    #[test]
+    fn output_style() {
+        let style = Style {
+            indent: Indent::Tabs,
+            blank_lines: 0,
+            brace_style: BraceStyle::NextLine,
+            trailing_newline: false,
+            ..Style::default()
+        };
+        let code = compile_with("$(b!2;$b;\n$L3;\nc!}$1;\nL;\n$c", &Options { style, ..Options::default() });
+        assert!(code.contains("_scgt_print = (v) {\n\t$.print(v)\n\treturn v\n}\n// $(b!2;$b;\n"));
+        assert!(code.contains("$.print(()\n{\n\t// b!2;\n"));
+        assert!(code.contains("\twhile true\n\t{\n"));
+        assert!(code.contains("\nlet c = !{\n\t// $1\n"));
+        assert!(code.contains("\nwhile true\n{ }\n"));
+        assert!(!code.ends_with('\n'));
+    }
+
+    #[test]
+    fn comment_modes() {
+        let with_comments = |comments| {
+            let style = Style { comments, ..Style::default() };
+            compile_with("a!2;\n$(b!2;$b", &Options { style, ..Options::default() })
+        };
+
+        let code = with_comments(Comments::None);
+        assert!(!code.contains("// a") && !code.contains("// $") && !code.contains("// b"));
+
+        let code = with_comments(Comments::TopLevelOnly);
+        assert!(code.contains("// $(b!2;$b\n$.print(() {\n    let b = 2\n"));
+
+        let code = with_comments(Comments::Span);
+        assert!(code.contains("// 1:1\nlet a = 2\n"));
+        assert!(code.contains("// 2:1\n$.print(() {\n    // 2:3\n"));
+    }
+
+    #[test]
+    fn statement_mappings() {
+        let code = "a!2;\n$(b!2;$b";
+        let ast = crate::parser::parse_test_code(code);
+        let output = generate(&ast, code, &Options::default()).with_helpers(&HelperImpls::builtin(Target::V0_8)).unwrap();
+
+        let mapped = output.mappings
+            .iter()
+            .map(|mapping| (&output.code[mapping.generated.clone()], &code[mapping.source.clone()]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(mapped[0], ("let a = 2", "a!2;"));
+        assert_eq!(mapped[2], ("let b = 2", "b!2;"));
+        assert_eq!(mapped[3], ("return _scgt_print(b)", "$b"));
+    }
+
+    #[test]
+    fn macro_params() {
+        let code = compile("$X$x");
+        assert!(!code.contains("let x"));
+    }
+}
@@ -0,0 +1,109 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::style::{BraceStyle, Comments, Indent, Style};
+use crate::target::Target;
+use crate::util::helpers::DEFAULT_PREFIX;
+
+/// Name of the configuration file, which is looked up in the current directory.
+pub const FILE_NAME: &str = "scgt.toml";
+
+/// Settings from the configuration file. Command line arguments take precedence over these.
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// SPWN version to generate code for.
+    pub target: Option<Target>,
+    /// Directory with helper implementations to use instead of the built-in ones.
+    pub helpers_dir: Option<PathBuf>,
+    /// Prefix of generated identifiers.
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub style: StyleConfig,
+}
+
+/// The `[style]` table, see [`crate::style::Style`].
+#[derive(Deserialize, Default, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StyleConfig {
+    pub indent_size: Option<usize>,
+    pub tabs: Option<bool>,
+    pub blank_lines: Option<usize>,
+    pub brace_style: Option<BraceStyle>,
+    pub trailing_newline: Option<bool>,
+    pub comments: Option<Comments>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        match fs::read_to_string(FILE_NAME) {
+            Ok(text) => toml::from_str(&text).map_err(|err| format!("Invalid {FILE_NAME}: {}", err.message())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(format!("Could not read {FILE_NAME}: {err}")),
+        }
+    }
+
+    /// Prefix of generated identifiers, or the default one if it isn't set.
+    pub fn prefix(&self) -> Result<String, String> {
+        match &self.prefix {
+            Some(prefix) => parse_prefix(prefix).map_err(|err| format!("Invalid prefix in {FILE_NAME}: {err}")),
+            None => Ok(DEFAULT_PREFIX.to_string()),
+        }
+    }
+}
+
+impl StyleConfig {
+    /// Output style with the default for every key that isn't set.
+    pub fn style(&self) -> Style {
+        let default = Style::default();
+
+        let indent = match (self.tabs, self.indent_size) {
+            (Some(true), _) => Indent::Tabs,
+            (_, Some(n)) => Indent::Spaces(n),
+            _ => default.indent,
+        };
+
+        Style {
+            indent,
+            blank_lines: self.blank_lines.unwrap_or(default.blank_lines),
+            brace_style: self.brace_style.unwrap_or(default.brace_style),
+            trailing_newline: self.trailing_newline.unwrap_or(default.trailing_newline),
+            comments: self.comments.unwrap_or(default.comments),
+        }
+    }
+}
+
+/// Checks that a prefix can start identifiers, for the `prefix` key and the `--prefix` argument.
+pub fn parse_prefix(prefix: &str) -> Result<String, String> {
+    let mut chars = prefix.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(prefix.to_string())
+    } else {
+        Err(String::from("must be the start of an identifier"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let config: Config = toml::from_str("helpers-dir = \"helpers\"").unwrap();
+        assert_eq!(config.helpers_dir, Some(PathBuf::from("helpers")));
+        assert!(toml::from_str::<Config>("helper-dir = \"helpers\"").is_err());
+
+        let config: Config = toml::from_str("[style]\ntabs = true\nbrace-style = \"next-line\"").unwrap();
+        assert_eq!(config.style.tabs, Some(true));
+        assert!(matches!(config.style.brace_style, Some(BraceStyle::NextLine)));
+        assert!(matches!(config.style.style().indent, Indent::Tabs));
+
+        let config: Config = toml::from_str("target = \"1.0\"\nprefix = \"1a\"").unwrap();
+        assert_eq!(config.target, Some(Target::V0_9));
+        assert_eq!(config.prefix().unwrap_err(), "Invalid prefix in scgt.toml: must be the start of an identifier");
+    }
+}
@@ -0,0 +1,174 @@
+//! Readable breakdown of SCGT code for `explain`, using the categories from `syntax.md`.
+
+use crate::ast::{Constant, Expr, ExprKind};
+use crate::codegen::PrintBehavior;
+use crate::util::first_line;
+
+/// What happens to the value of the last statement in a list.
+#[derive(Clone, Copy)]
+enum Last {
+    Printed,
+    Returned,
+    Collected,
+}
+
+struct Line {
+    depth: usize,
+    code: String,
+    comment: String,
+}
+
+/// Lists every construct in the code with its source on the left and a comment explaining it on the right.
+/// Child constructs are indented below their parent.
+pub fn explain(stmts: &[Expr], source: &str) -> String {
+    let mut lines = Vec::new();
+    explain_stmts(stmts, Last::Printed, source, 0, &mut lines);
+
+    let code = |line: &Line| format!("{}{}", "    ".repeat(line.depth), line.code);
+    let width = lines.iter().map(|line| code(line).chars().count()).max().unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            let code = code(line);
+            let padding = " ".repeat(width - code.chars().count());
+            format!("{code}{padding}  // {}", line.comment)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn explain_stmts(stmts: &[Expr], last: Last, source: &str, depth: usize, lines: &mut Vec<Line>) {
+    for (i, stmt) in stmts.iter().enumerate() {
+        let (note, is_stmt) = match last {
+            Last::Returned if i + 1 == stmts.len() => (Some("returned"), false),
+            Last::Collected if i + 1 == stmts.len() => (Some("collected"), false),
+            _ => match stmt.print_behavior() {
+                PrintBehavior::Implicit => (Some("printed"), true),
+                PrintBehavior::Explicit => (None, true),
+            },
+        };
+
+        explain_expr(stmt, note, is_stmt, source, depth, lines);
+    }
+}
+
+fn explain_expr(expr: &Expr, note: Option<&str>, is_stmt: bool, source: &str, depth: usize, lines: &mut Vec<Line>) {
+    let code = &source[expr.span.into_range()];
+    let (category, description) = describe(expr, code, is_stmt);
+
+    let mut comment = format!("{category}: {description}");
+    if let Some(note) = note {
+        comment = format!("{comment} ({note})");
+    }
+
+    lines.push(Line { depth, code: first_line(code), comment });
+
+    let depth = depth + 1;
+    match &expr.kind {
+        ExprKind::Block(stmts) | ExprKind::Macro { body: stmts, .. } => {
+            explain_stmts(stmts, Last::Returned, source, depth, lines)
+        }
+        ExprKind::TriggerFunction(stmts) => explain_stmts(stmts, Last::Printed, source, depth, lines),
+        ExprKind::Loop(stmts) => {
+            let last = if is_stmt { Last::Printed } else { Last::Collected };
+            explain_stmts(stmts, last, source, depth, lines)
+        }
+        _ => expr.for_each_child(|child| explain_expr(child, None, false, source, depth, lines)),
+    }
+}
+
+/// Category in `syntax.md` and description of an expression.
+fn describe(expr: &Expr, code: &str, is_stmt: bool) -> (&'static str, String) {
+    match &expr.kind {
+        ExprKind::Number(n) => ("value", format!("number {n}")),
+        ExprKind::String(s) if code.starts_with('\'') => ("value", format!("string {s:?} of a single character")),
+        ExprKind::String(s) => ("value", format!("string {s:?}")),
+        ExprKind::TypeIndicator(name) => ("value", format!("type indicator @{name}")),
+        ExprKind::Constant(c) => ("value", format!("{}, SPWN `{}`", constant_name(*c), c.spwn_code())),
+        ExprKind::LoopVariable(c) => ("value", format!("loop variable {c}")),
+        ExprKind::Ident(name) => ("identifier", format!("variable `{name}`")),
+
+        ExprKind::ShortMultiplication(n, _) => ("prefix", format!("multiplies the following value by {n}")),
+        ExprKind::Invert(_) => ("prefix", String::from("inverts the following value")),
+        ExprKind::Print(_) => ("prefix", String::from("prints the following value and returns it")),
+        ExprKind::OnTouch(_) => ("value", String::from("runs the following value on touch, roughly SPWN `on(touch(), v)`")),
+
+        ExprKind::Block(_) => ("value", String::from("block evaluated as a value")),
+        ExprKind::TriggerFunction(_) => ("value", String::from("trigger function")),
+        ExprKind::Macro { params, .. } => match params.as_slice() {
+            [] => ("value", String::from("macro with no arguments")),
+            [param] if code.starts_with('X') => ("value", format!("macro with argument `{param}`, same as `){param};`")),
+            params => ("value", format!("macro with arguments {}", params.iter().map(|p| format!("`{p}`")).collect::<Vec<_>>().join(", "))),
+        },
+        ExprKind::Loop(_) if is_stmt => ("value", String::from("infinite loop")),
+        ExprKind::Loop(_) => ("value", String::from("infinite loop collecting the values of its last statement")),
+
+        ExprKind::Assign { target, value } => {
+            let name = format!("`{}`", &code[target.span.start - expr.span.start..target.span.end - expr.span.start]);
+
+            if matches!(value.kind, ExprKind::Macro { .. }) && target.span.end == value.span.start {
+                ("postfix", format!("defines the macro {name}, same as assigning it with `!`"))
+            } else {
+                ("postfix", format!("assigns the value on the right to {name} and returns it"))
+            }
+        }
+        ExprKind::MemberAccess { name, .. } => ("postfix", format!("accesses the child `{name}`")),
+        ExprKind::MacroCallNoArgs(_) => ("postfix", String::from("calls a macro without arguments or a trigger function")),
+    }
+}
+
+fn constant_name(constant: Constant) -> &'static str {
+    match constant {
+        Constant::EmptyArray => "empty array",
+        Constant::Block => "unused block ID",
+        Constant::Color => "unused color ID",
+        Constant::Item => "unused item ID",
+        Constant::False => "false",
+        Constant::Group => "unused group ID",
+        Constant::Null => "null",
+        Constant::EmptyString => "empty string",
+        Constant::True => "true",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn explain_code(code: &str) -> String {
+        let ast = crate::parser::parse_test_code(code);
+        explain(&ast, code)
+    }
+
+    #[test]
+    fn nested_constructs() {
+        assert_eq!(explain_code("a!X$x;;\n2'b"), [
+            "a!X$x;;        // postfix: assigns the value on the right to `a` and returns it",
+            "    a          // identifier: variable `a`",
+            "    X$x;       // value: macro with argument `x`, same as `)x;`",
+            "        $x     // prefix: prints the following value and returns it (returned)",
+            "            x  // identifier: variable `x`",
+            "2'b            // prefix: multiplies the following value by 2 (printed)",
+            "    'b         // value: string \"b\" of a single character",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn statement_values() {
+        assert_eq!(explain_code("fM$T;\nb!(L1;;\nL2"), [
+            "fM$T;          // postfix: defines the macro `f`, same as assigning it with `!`",
+            "    f          // identifier: variable `f`",
+            "    M$T;       // value: macro with no arguments",
+            "        $T     // prefix: prints the following value and returns it (returned)",
+            "            T  // value: true, SPWN `true`",
+            "b!(L1;;        // postfix: assigns the value on the right to `b` and returns it",
+            "    b          // identifier: variable `b`",
+            "    (L1;;      // value: block evaluated as a value",
+            "        L1;    // value: infinite loop collecting the values of its last statement (returned)",
+            "            1  // value: number 1 (collected)",
+            "L2             // value: infinite loop",
+            "    2          // value: number 2 (printed)",
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::ast::*;
+
+/// Evaluates operations on literals at compile time.
+pub fn fold(stmts: &mut [Expr]) {
+    stmts.iter_mut().for_each(fold_stmt);
+}
+
+enum Value {
+    Number(f64),
+    Bool(bool),
+    String(String),
+    EmptyArray,
+}
+
+impl Value {
+    fn of(expr: &Expr) -> Option<Self> {
+        match &expr.kind {
+            ExprKind::Number(n) => n.parse().ok().map(Self::Number),
+            ExprKind::String(s) => Some(Self::String(s.clone())),
+            ExprKind::Constant(Constant::True) => Some(Self::Bool(true)),
+            ExprKind::Constant(Constant::False) => Some(Self::Bool(false)),
+            ExprKind::Constant(Constant::EmptyString) => Some(Self::String(String::new())),
+            ExprKind::Constant(Constant::EmptyArray) => Some(Self::EmptyArray),
+            _ => None,
+        }
+    }
+
+    fn into_kind(self) -> ExprKind {
+        match self {
+            Self::Number(n) => ExprKind::Number(format_number(n)),
+            Self::Bool(true) => ExprKind::Constant(Constant::True),
+            Self::Bool(false) => ExprKind::Constant(Constant::False),
+            Self::String(s) if s.is_empty() => ExprKind::Constant(Constant::EmptyString),
+            Self::String(s) => ExprKind::String(s),
+            Self::EmptyArray => ExprKind::Constant(Constant::EmptyArray),
+        }
+    }
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{n}")
+    }
+}
+
+/// Folds an expression whose value is discarded, which must keep its print behavior.
+fn fold_stmt(stmt: &mut Expr) {
+    match &mut stmt.kind {
+        // a macro call that became a block would be printed
+        ExprKind::MacroCallNoArgs(value) => fold_expr(value),
+        _ => fold_expr(stmt),
+    }
+}
+
+pub(crate) fn fold_expr(expr: &mut Expr) {
+    match &mut expr.kind {
+        ExprKind::Block(stmts)
+        | ExprKind::TriggerFunction(stmts)
+        | ExprKind::Macro { body: stmts, .. }
+        | ExprKind::Loop(stmts) => stmts.iter_mut().for_each(fold_stmt),
+        _ => expr.for_each_child_mut(fold_expr),
+    }
+
+    let folded = match &mut expr.kind {
+        ExprKind::ShortMultiplication(n, value) => {
+            let n = n.parse::<f64>().ok();
+            let value = Value::of(value);
+
+            n.zip(value).and_then(|(n, value)| match value {
+                Value::Number(m) => Some(Value::Number(n * m)),
+                Value::Bool(b) => Some(Value::Number(if b { n } else { 0.0 })),
+                // only fold strings if the result is not longer than the multiplication itself
+                Value::String(s) if n.fract() == 0.0 && n >= 0.0 => {
+                    let len = s.chars().count();
+                    (n as usize * len <= len + format_number(n).len() + 3)
+                        .then(|| Value::String(s.repeat(n as usize)))
+                }
+                Value::String(_) => None,
+                Value::EmptyArray => Some(Value::EmptyArray),
+            })
+            .map(Value::into_kind)
+        }
+
+        ExprKind::Invert(value) => Value::of(value)
+            .map(|value| match value {
+                Value::Number(n) => Value::Number(-n),
+                Value::Bool(b) => Value::Bool(!b),
+                Value::String(s) => Value::String(s.chars().rev().collect()),
+                Value::EmptyArray => Value::EmptyArray,
+            })
+            .map(Value::into_kind),
+
+        ExprKind::Block(stmts) => match stmts.as_mut_slice() {
+            [stmt] if Value::of(stmt).is_some() => Some(std::mem::replace(&mut stmt.kind, ExprKind::Block(Vec::new()))),
+            _ => None,
+        },
+
+        // calling a macro literal without arguments is the same as evaluating its body
+        ExprKind::MacroCallNoArgs(value) => match &mut value.kind {
+            ExprKind::Macro { params, body } if params.is_empty() => Some(ExprKind::Block(std::mem::take(body))),
+            _ => None,
+        },
+
+        _ => None,
+    };
+
+    if let Some(kind) = folded {
+        expr.kind = kind;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fold_last(code: &str) -> Expr {
+        let mut ast = crate::parser::parse_test_code(code);
+        fold(&mut ast);
+        ast.pop().expect("test code should not be empty")
+    }
+
+    fn assert_folds(code: &str, expected: ExprKind) {
+        let folded = fold_last(code).kind;
+        match (&folded, &expected) {
+            (ExprKind::Number(a), ExprKind::Number(b)) => assert_eq!(a, b),
+            (ExprKind::String(a), ExprKind::String(b)) => assert_eq!(a, b),
+            (ExprKind::Constant(a), ExprKind::Constant(b)) if a == b => (),
+            _ => panic!("`{code}` was not folded as expected"),
+        }
+    }
+
+    #[test]
+    fn numbers() {
+        assert_folds("!3", ExprKind::Number(String::from("-3")));
+        assert_folds("!!3", ExprKind::Number(String::from("3")));
+        assert_folds("2!3", ExprKind::Number(String::from("-6")));
+        assert_folds("3T", ExprKind::Number(String::from("3")));
+    }
+
+    #[test]
+    fn strings_and_bools() {
+        assert_folds("2'a", ExprKind::String(String::from("aa")));
+        assert_folds("!`abc`", ExprKind::String(String::from("cba")));
+        assert_folds("!T", ExprKind::Constant(Constant::False));
+        assert_folds("!S", ExprKind::Constant(Constant::EmptyString));
+        assert!(matches!(fold_last("9'a").kind, ExprKind::ShortMultiplication(..)));
+    }
+
+    #[test]
+    fn blocks_and_calls() {
+        assert_folds("(3", ExprKind::Number(String::from("3")));
+        assert!(matches!(fold_last("M$3;M").kind, ExprKind::MacroCallNoArgs(_)));
+        assert!(matches!(fold_last("$M$3;M").kind, ExprKind::Print(value) if matches!(value.kind, ExprKind::Block(_))));
+        assert!(matches!(fold_last("X$3;M").kind, ExprKind::MacroCallNoArgs(_)));
+    }
+}
@@ -0,0 +1,257 @@
+//! Printing parsed SCGT code back as SCGT, either spread out for reading or as short as possible.
+
+use crate::ast::{same_stmts, Expr, ExprKind};
+use crate::parser;
+
+/// Choices between equivalent ways of writing the same code.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Encoding {
+    /// `'c` instead of `` `c` `` for strings of a single character.
+    pub char_literals: bool,
+    /// `aMb` and `aXb` instead of assigning macros with `!`.
+    pub named_macros: bool,
+}
+
+impl Encoding {
+    pub const SHORTEST: Self = Self { char_literals: true, named_macros: true };
+}
+
+/// Token at the end of the output so far that could be continued by the next character.
+#[derive(Clone, Copy)]
+enum OpenToken {
+    Number,
+    /// Identifier matching `[a-z]+`.
+    Ident,
+    /// Identifier written with a leading `_`.
+    UnderscoreIdent,
+}
+
+impl OpenToken {
+    fn continued_by(&self, c: char) -> bool {
+        match self {
+            Self::Number => c.is_ascii_digit() || c == '.',
+            Self::Ident => c.is_ascii_lowercase(),
+            Self::UnderscoreIdent => c.is_ascii_alphanumeric() || c == '_',
+        }
+    }
+}
+
+struct Printer {
+    encoding: Encoding,
+    code: String,
+    open_token: Option<OpenToken>,
+}
+
+impl Printer {
+    fn new(encoding: Encoding) -> Self {
+        Self { encoding, code: String::new(), open_token: None }
+    }
+
+    fn push(&mut self, code: &str) {
+        if let (Some(token), Some(c)) = (self.open_token.take(), code.chars().next()) {
+            if token.continued_by(c) {
+                self.code.push(' ');
+            }
+        }
+        self.code.push_str(code);
+    }
+
+    fn push_token(&mut self, code: &str, token: OpenToken) {
+        self.push(code);
+        self.open_token = Some(token);
+    }
+
+    fn ident(&mut self, name: &str) {
+        if name.chars().all(|c| c.is_ascii_lowercase()) {
+            self.push_token(name, OpenToken::Ident);
+        } else {
+            self.push_token(&format!("_{name}"), OpenToken::UnderscoreIdent);
+        }
+    }
+
+    fn stmts(&mut self, stmts: &[Expr]) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            if i > 0 {
+                self.push("\n");
+            }
+            self.expr(stmt);
+        }
+    }
+
+    fn body(&mut self, start: &str, stmts: &[Expr]) {
+        self.push(start);
+        self.stmts(stmts);
+        self.push(";");
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Number(n) => self.push_token(number_code(n), OpenToken::Number),
+            ExprKind::String(s) => match s.chars().collect::<Vec<_>>().as_slice() {
+                &[c] if self.encoding.char_literals && !matches!(c, '\n' | '\r') => self.push(&format!("'{c}")),
+                _ => self.push(&format!("`{}`", escape(s))),
+            },
+            ExprKind::TypeIndicator(name) => {
+                self.push("@");
+                self.ident(name);
+            }
+            ExprKind::Constant(c) => self.push(&c.letter().to_string()),
+            ExprKind::LoopVariable(c) => self.push(&c.to_string()),
+            ExprKind::Ident(name) => self.ident(name),
+
+            ExprKind::ShortMultiplication(n, expr) => {
+                self.push_token(number_code(n), OpenToken::Number);
+                self.expr(expr);
+            }
+            ExprKind::Invert(expr) => {
+                self.push("!");
+                self.expr(expr);
+            }
+            ExprKind::Print(expr) => {
+                self.push("$");
+                self.expr(expr);
+            }
+            ExprKind::OnTouch(expr) => {
+                self.push("E");
+                self.expr(expr);
+                self.push(";");
+            }
+
+            ExprKind::Block(stmts) => self.body("(", stmts),
+            ExprKind::TriggerFunction(stmts) => self.body("}", stmts),
+            ExprKind::Macro { params, body } => match params.as_slice() {
+                [] => self.body("M", body),
+                [param] if param == "x" => self.body("X", body),
+                // not supported by the parser yet, so this is caught when checking the result
+                params => self.body(&format!("){};", params.join(",")), body),
+            },
+            ExprKind::Loop(stmts) => self.body("L", stmts),
+
+            ExprKind::Assign { target, value } => match (&target.kind, &value.kind) {
+                (ExprKind::Ident(_), ExprKind::Macro { .. }) if self.encoding.named_macros => {
+                    self.expr(target);
+                    self.expr(value);
+                }
+                _ => {
+                    self.expr(target);
+                    self.push("!");
+                    self.expr(value);
+                    self.push(";");
+                }
+            },
+            ExprKind::MemberAccess { value, name } => {
+                self.expr(value);
+                self.push(".");
+                self.ident(name);
+            }
+            ExprKind::MacroCallNoArgs(value) => {
+                self.expr(value);
+                self.push("M");
+            }
+        }
+    }
+}
+
+/// Number as it has to be written, since a leading `0.` would be read as a short multiplication by 0.
+fn number_code(n: &str) -> &str {
+    n.strip_prefix('0').filter(|n| n.starts_with('.')).unwrap_or(n)
+}
+
+fn escape(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\n' => String::from("\\n"),
+            '\r' => String::from("\\r"),
+            '\t' => String::from("\\t"),
+            '`' => String::from("\\`"),
+            '\\' => String::from("\\\\"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Whether the code parses without errors to the same statements.
+pub fn parses_to(code: &str, stmts: &[Expr]) -> bool {
+    let result = parser::parse(code);
+    !result.has_errors() && result.output().is_some_and(|output| same_stmts(output, stmts))
+}
+
+/// Prints the code with one statement per line and every closing written out,
+/// checking that it parses to the same statements.
+pub fn print(stmts: &[Expr], encoding: Encoding) -> Result<String, String> {
+    let mut printer = Printer::new(encoding);
+    printer.stmts(stmts);
+    let code = printer.code;
+
+    if parses_to(&code, stmts) {
+        Ok(code)
+    } else {
+        Err(String::from("The code contains constructs that can't be written back without changing their meaning"))
+    }
+}
+
+/// Prints the code with one statement per line, every closing written out and spaces only where needed.
+pub fn format(stmts: &[Expr]) -> Result<String, String> {
+    print(stmts, Encoding::default()).map(|code| format!("{code}\n"))
+}
+
+/// Prints the code as short as possible without changing how it parses.
+pub fn golf(stmts: &[Expr]) -> Result<String, String> {
+    golf_with(stmts, Encoding::SHORTEST)
+}
+
+/// Prints the code without any `;`, space, newline or closing backtick that can be left out.
+pub fn golf_with(stmts: &[Expr], encoding: Encoding) -> Result<String, String> {
+    let mut code = print(stmts, encoding)?;
+
+    // removing characters from the end first keeps the remaining indices valid
+    let optional = code
+        .char_indices()
+        .rev()
+        .filter(|&(_, c)| matches!(c, ';' | ' ' | '\n' | '`'))
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+
+    for i in optional {
+        let mut shorter = code.clone();
+        shorter.remove(i);
+        if parses_to(&shorter, stmts) {
+            code = shorter;
+        }
+    }
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        assert_eq!(format(&parser::parse_test_code("a.b!XL(L$TLxF")).unwrap(), "a.b!XL(L$T\nLx\nF;;;;;;\n");
+        assert_eq!(format(&parser::parse_test_code("fM1\n2")).unwrap(), "f!M1\n2;;\n");
+        assert_eq!(format(&parser::parse_test_code("2 3'a\n_aB.b\n'`")).unwrap(), "2 3`a`\n_aB.b\n`\\``\n");
+    }
+
+    #[test]
+    fn golfed() {
+        assert_eq!(golf(&parser::parse_test_code("a.b!XL(L$TLxF;;;;;")).unwrap(), "a.b!XL(L$TLxF");
+        assert_eq!(golf(&parser::parse_test_code("f!M1;;\n$f")).unwrap(), "fM1;$f");
+        assert_eq!(golf(&parser::parse_test_code("$`a b`")).unwrap(), "$`a b");
+        assert_eq!(golf(&parser::parse_test_code("$`a`\n$`\\n`")).unwrap(), "$'a$`\\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        for code in ["a.b!L", "a.b!L;", "a.b!L;3", "a.b!XL(L$TLxF", "$Ma!3;;M\n$a", "2 .5'x\n!@a\n}E1;"] {
+            let stmts = parser::parse_test_code(code);
+            let formatted = format(&stmts).expect(code);
+            let golfed = golf(&stmts).expect(code);
+
+            assert!(parses_to(&formatted, &stmts), "{formatted}");
+            assert!(parses_to(&golfed, &stmts), "{golfed}");
+            assert!(golfed.len() <= code.len(), "{code} became {golfed}");
+        }
+    }
+}
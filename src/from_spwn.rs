@@ -0,0 +1,807 @@
+//! Converting a practical subset of SPWN back to SCGT, for `from-spwn`.
+//!
+//! Statements outside of the subset are written as `//` comments so they can be ported by hand.
+
+use std::ops::Range;
+
+use crate::ast::{Constant, Expr, ExprKind};
+use crate::codegen::PrintBehavior;
+use crate::format::{self, Encoding};
+use crate::golf;
+use crate::util::diagnostics::{Diagnostic, Severity};
+
+const PUNCTUATION: [&str; 39] = [
+    "==", "!=", "<=", ">=", "=>", "->", "&&", "||", "+=", "-=", "*=", "/=", "..",
+    "(", ")", "{", "}", "[", "]", ",", ".", ":", ";", "=", "+", "-", "*", "/", "%",
+    "<", ">", "!", "?", "$", "@", "&", "|", "^", "#",
+];
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(String),
+    String(String),
+    /// Identifier or keyword.
+    Ident(String),
+    Newline,
+    Punct(&'static str),
+    Other(char),
+}
+
+fn tokenize(source: &str) -> Vec<(Token, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while let Some(c) = source[i..].chars().next() {
+        let start = i;
+        let rest = &source[i..];
+
+        let token = if c == '\n' {
+            i += 1;
+            Token::Newline
+        } else if c.is_whitespace() {
+            i += c.len_utf8();
+            continue;
+        } else if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            i += comment.find("*/").map_or(rest.len(), |end| end + 4);
+            continue;
+        } else if c.is_ascii_digit() {
+            let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit() && c != '_').unwrap_or(s.len());
+            let mut len = digits(rest);
+            if rest[len..].starts_with('.') && rest[len + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                len += 1 + digits(&rest[len + 1..]);
+            }
+            i += len;
+            Token::Number(rest[..len].replace('_', ""))
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            i += len;
+            Token::Ident(rest[..len].to_string())
+        } else if let Some((s, len)) = string(rest) {
+            i += len;
+            Token::String(s)
+        } else if let Some(punct) = PUNCTUATION.into_iter().find(|punct| rest.starts_with(punct)) {
+            i += punct.len();
+            Token::Punct(punct)
+        } else {
+            i += c.len_utf8();
+            Token::Other(c)
+        };
+
+        tokens.push((token, start..i));
+    }
+
+    tokens
+}
+
+/// String literal at the start of the code with its escapes resolved, along with its length.
+fn string(code: &str) -> Option<(String, usize)> {
+    let quote = code.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let mut s = String::new();
+    let mut chars = code.char_indices().skip(1);
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((s, i + 1)),
+            '\\' => s.push(match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                c => c,
+            }),
+            '\n' => return None,
+            c => s.push(c),
+        }
+    }
+
+    None
+}
+
+struct Node {
+    kind: Spwn,
+    span: Range<usize>,
+}
+
+enum Spwn {
+    Number(String),
+    String(String),
+    Bool(bool),
+    Null,
+    /// `?g`, `?c`, `?b` or `?i`
+    UnusedId(char),
+    TypeIndicator(String),
+    Ident(String),
+    /// `$`
+    Builtins,
+    EmptyArray,
+
+    /// `-v` or `!v`
+    Unary(Box<Node>),
+    Binary(Box<Node>, &'static str, Box<Node>),
+    Member(Box<Node>, String),
+    Call(Box<Node>, Vec<Node>),
+    /// `v!`
+    TriggerCall(Box<Node>),
+
+    Macro { params: Vec<String>, body: Vec<Stmt> },
+    TriggerFunction(Vec<Stmt>),
+
+    /// Value that was skipped when parsing, with the reason it can't be converted.
+    Unsupported(&'static str),
+}
+
+struct Stmt {
+    kind: StmtKind,
+    span: Range<usize>,
+}
+
+enum StmtKind {
+    Assign(Node, Node),
+    Expr(Node),
+    While(Node, Vec<Stmt>),
+    Return(Option<Node>),
+    /// Statement that doesn't parse as part of the subset.
+    Unsupported,
+}
+
+/// Binary operators from lowest to highest precedence.
+const BINARY_OPERATORS: [&[&str]; 5] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<", ">", "<=", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'a> {
+    tokens: &'a [(Token, Range<usize>)],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn at(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn at_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == name)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.at(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Option<()> {
+        self.eat(punct).then_some(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// Span from the token at `start` to the last token consumed.
+    fn span_from(&self, start: usize) -> Range<usize> {
+        let end = self.tokens[..self.pos].last().map_or(0, |(_, span)| span.end);
+        self.tokens.get(start).map_or(end, |(_, span)| span.start)..end
+    }
+
+    fn at_stmt_end(&self, closing: Option<&str>) -> bool {
+        match self.peek() {
+            None | Some(Token::Newline) => true,
+            Some(Token::Punct(punct)) => *punct == ";" || Some(*punct) == closing,
+            _ => false,
+        }
+    }
+
+    fn stmts(&mut self, closing: Option<&str>) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+
+        loop {
+            while self.peek() == Some(&Token::Newline) || self.eat(";") {
+                self.skip_newlines();
+            }
+            if self.peek().is_none() || closing.is_some_and(|closing| self.at(closing)) {
+                return stmts;
+            }
+
+            let start = self.pos;
+            let kind = match self.stmt() {
+                Some(kind) if self.at_stmt_end(closing) => kind,
+                _ => {
+                    self.pos = start;
+                    self.skip_stmt(closing);
+                    StmtKind::Unsupported
+                }
+            };
+            stmts.push(Stmt { kind, span: self.span_from(start) });
+        }
+    }
+
+    /// Skips to the end of the statement, keeping brackets balanced.
+    fn skip_stmt(&mut self, closing: Option<&str>) {
+        let mut depth = 0usize;
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Punct("(" | "[" | "{") => depth += 1,
+                Token::Punct(")" | "]" | "}") if depth > 0 => depth -= 1,
+                Token::Punct(punct) if Some(*punct) == closing => return,
+                Token::Newline | Token::Punct(";") if depth == 0 => return,
+                _ => (),
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Skips a bracketed value, starting at its opening bracket.
+    fn skip_group(&mut self) -> Option<()> {
+        let mut depth = 0usize;
+
+        loop {
+            match self.next()? {
+                Token::Punct("(" | "[" | "{") => depth += 1,
+                Token::Punct(")" | "]" | "}") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(());
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn stmt(&mut self) -> Option<StmtKind> {
+        if self.at_ident("let") || self.at_ident("mut") {
+            self.pos += 1;
+            let start = self.pos;
+            let Some(Token::Ident(name)) = self.next() else { return None };
+            let target = Node { kind: Spwn::Ident(name.clone()), span: self.span_from(start) };
+
+            self.expect("=")?;
+            self.skip_newlines();
+            return Some(StmtKind::Assign(target, self.expr()?));
+        }
+
+        if self.at_ident("while") {
+            self.pos += 1;
+            let condition = self.expr()?;
+            return Some(StmtKind::While(condition, self.braced_stmts()?));
+        }
+
+        if self.at_ident("return") {
+            self.pos += 1;
+            return Some(StmtKind::Return(if self.at_stmt_end(Some("}")) { None } else { Some(self.expr()?) }));
+        }
+
+        let expr = self.expr()?;
+        if self.eat("=") {
+            self.skip_newlines();
+            return Some(StmtKind::Assign(expr, self.expr()?));
+        }
+        Some(StmtKind::Expr(expr))
+    }
+
+    fn braced_stmts(&mut self) -> Option<Vec<Stmt>> {
+        self.expect("{")?;
+        let stmts = self.stmts(Some("}"));
+        self.expect("}")?;
+        Some(stmts)
+    }
+
+    fn expr(&mut self) -> Option<Node> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Option<Node> {
+        let Some(operators) = BINARY_OPERATORS.get(level) else { return self.unary() };
+
+        let start = self.pos;
+        let mut left = self.binary(level + 1)?;
+
+        while let Some(&op) = operators.iter().find(|&&op| self.at(op)) {
+            self.pos += 1;
+            self.skip_newlines();
+            let right = self.binary(level + 1)?;
+            left = Node { kind: Spwn::Binary(Box::new(left), op, Box::new(right)), span: self.span_from(start) };
+        }
+
+        Some(left)
+    }
+
+    fn unary(&mut self) -> Option<Node> {
+        let start = self.pos;
+
+        for op in ["-", "!"] {
+            if self.at(op) && !(op == "!" && self.tokens.get(self.pos + 1).is_some_and(|(token, _)| *token == Token::Punct("{"))) {
+                self.pos += 1;
+                let value = self.unary()?;
+                return Some(Node { kind: Spwn::Unary(Box::new(value)), span: self.span_from(start) });
+            }
+        }
+
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Option<Node> {
+        let start = self.pos;
+        let mut value = self.primary()?;
+
+        loop {
+            let kind = if self.eat(".") {
+                let Some(Token::Ident(name)) = self.next() else { return None };
+                Spwn::Member(Box::new(value), name.clone())
+            } else if self.eat("(") {
+                Spwn::Call(Box::new(value), self.list(")")?)
+            } else if self.eat("!") {
+                Spwn::TriggerCall(Box::new(value))
+            } else {
+                return Some(value);
+            };
+            value = Node { kind, span: self.span_from(start) };
+        }
+    }
+
+    /// Comma separated values after an opening bracket, up to the closing one.
+    fn list(&mut self, closing: &str) -> Option<Vec<Node>> {
+        let mut values = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.eat(closing) {
+                return Some(values);
+            }
+            values.push(self.expr()?);
+            self.skip_newlines();
+            if !self.eat(",") {
+                self.expect(closing)?;
+                return Some(values);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Option<Node> {
+        let start = self.pos;
+
+        let kind = match self.peek()? {
+            Token::Number(n) => {
+                self.pos += 1;
+                Spwn::Number(n.clone())
+            }
+            Token::String(s) => {
+                self.pos += 1;
+                Spwn::String(s.clone())
+            }
+            Token::Ident(name) => {
+                self.pos += 1;
+                match name.as_str() {
+                    "true" => Spwn::Bool(true),
+                    "false" => Spwn::Bool(false),
+                    "null" => Spwn::Null,
+                    "obj" | "trigger" if self.at("{") => {
+                        self.skip_group()?;
+                        Spwn::Unsupported("Objects aren't supported by the SCGT parser yet")
+                    }
+                    "let" | "mut" | "while" | "for" | "if" | "else" | "return" | "break" | "continue" | "import" => return None,
+                    _ => Spwn::Ident(name.clone()),
+                }
+            }
+            Token::Punct("?") => {
+                self.pos += 1;
+                match self.next()? {
+                    Token::Ident(id) if matches!(id.as_str(), "g" | "c" | "b" | "i") => Spwn::UnusedId(id.chars().next()?),
+                    _ => return None,
+                }
+            }
+            Token::Punct("@") => {
+                self.pos += 1;
+                let Some(Token::Ident(name)) = self.next() else { return None };
+                Spwn::TypeIndicator(name.clone())
+            }
+            Token::Punct("$") => {
+                self.pos += 1;
+                Spwn::Builtins
+            }
+            Token::Punct("[") if self.tokens.get(self.pos + 1).is_some_and(|(token, _)| *token == Token::Punct("]")) => {
+                self.pos += 2;
+                Spwn::EmptyArray
+            }
+            Token::Punct("[") => {
+                self.skip_group()?;
+                Spwn::Unsupported("Arrays with elements aren't supported by the SCGT parser yet")
+            }
+            Token::Punct("{") => {
+                self.skip_group()?;
+                Spwn::Unsupported("Dictionaries aren't supported by the SCGT parser yet")
+            }
+            Token::Punct("!") => {
+                self.pos += 1;
+                Spwn::TriggerFunction(self.braced_stmts()?)
+            }
+            Token::Punct("(") => return self.parenthesized(),
+            _ => return None,
+        };
+
+        Some(Node { kind, span: self.span_from(start) })
+    }
+
+    /// Macro definition, parenthesized value or `()`.
+    fn parenthesized(&mut self) -> Option<Node> {
+        let start = self.pos;
+        self.expect("(")?;
+
+        let mut params = Vec::new();
+        let mut pos = self.pos;
+        while let Some((Token::Ident(name), _)) = self.tokens.get(pos) {
+            params.push(name.clone());
+            pos += 1;
+            if self.tokens.get(pos).is_some_and(|(token, _)| *token == Token::Punct(",")) {
+                pos += 1;
+            }
+        }
+
+        let is_macro = self.tokens.get(pos).is_some_and(|(token, _)| *token == Token::Punct(")"))
+            && self.tokens.get(pos + 1).is_some_and(|(token, _)| matches!(token, Token::Punct("{" | "=>")));
+
+        if is_macro {
+            self.pos = pos + 1;
+            let body = if self.eat("=>") {
+                self.skip_newlines();
+                let value = self.expr()?;
+                let span = value.span.clone();
+                vec![Stmt { kind: StmtKind::Return(Some(value)), span }]
+            } else {
+                self.braced_stmts()?
+            };
+            return Some(Node { kind: Spwn::Macro { params, body }, span: self.span_from(start) });
+        }
+
+        self.skip_newlines();
+        if self.eat(")") {
+            return Some(Node { kind: Spwn::Null, span: self.span_from(start) });
+        }
+
+        let value = self.expr()?;
+        self.skip_newlines();
+        self.expect(")")?;
+        Some(value)
+    }
+}
+
+/// Whether a statement's value is printed or returned.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Printed,
+    Returned,
+}
+
+/// Part of the code that can't be converted.
+struct Unsupported {
+    span: Range<usize>,
+    reason: &'static str,
+}
+
+/// Statement that was left out of the code because of an unsupported part.
+struct Skipped {
+    stmt: Range<usize>,
+    cause: Unsupported,
+}
+
+type Conversion<T> = Result<T, Unsupported>;
+
+fn unsupported<T>(node: &Node, reason: &'static str) -> Conversion<T> {
+    Err(Unsupported { span: node.span.clone(), reason })
+}
+
+fn new_expr(kind: ExprKind) -> Expr {
+    Expr::new(kind, (0..0).into())
+}
+
+fn is_implicitly_printed(expr: &Expr) -> bool {
+    matches!(expr.print_behavior(), PrintBehavior::Implicit)
+}
+
+#[derive(Default)]
+struct Converter {
+    /// Statements left out of the code so far.
+    skipped: Vec<Skipped>,
+}
+
+impl Converter {
+    /// Converts the statements of a body, leaving out the ones that can't be converted.
+    fn stmts(&mut self, stmts: &[Stmt], last: Position) -> Vec<Expr> {
+        let mut converted = Vec::new();
+
+        for (i, stmt) in stmts.iter().enumerate() {
+            let position = if i + 1 == stmts.len() { last } else { Position::Printed };
+            match self.stmt(stmt, position) {
+                Ok(expr) => converted.push(expr),
+                Err(cause) => {
+                    self.skipped.push(Skipped { stmt: stmt.span.clone(), cause });
+
+                    // the statement before would be returned instead of printed
+                    if position == Position::Returned {
+                        converted.push(new_expr(ExprKind::Constant(Constant::Null)));
+                    }
+                }
+            }
+        }
+
+        converted
+    }
+
+    /// Converts the body of a macro, which returns its last statement only if that is a `return`.
+    fn macro_body(&mut self, body: &[Stmt]) -> Vec<Expr> {
+        match body.last() {
+            None => Vec::new(),
+            Some(Stmt { kind: StmtKind::Return(_), .. }) => self.stmts(body, Position::Returned),
+            Some(_) => {
+                let mut stmts = self.stmts(body, Position::Printed);
+                stmts.push(new_expr(ExprKind::Constant(Constant::Null)));
+                stmts
+            }
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, position: Position) -> Conversion<Expr> {
+        match &stmt.kind {
+            StmtKind::Assign(target, value) => {
+                let target = self.expr(target)?;
+                if !matches!(target.kind, ExprKind::Ident(_) | ExprKind::MemberAccess { .. }) {
+                    return Err(Unsupported { span: stmt.span.clone(), reason: "Only variables and children can be assigned to" });
+                }
+                let value = self.expr(value)?;
+                Ok(new_expr(ExprKind::Assign { target: Box::new(target), value: Box::new(value) }))
+            }
+
+            StmtKind::While(condition, body) => match condition.kind {
+                Spwn::Bool(true) => Ok(new_expr(ExprKind::Loop(self.stmts(body, Position::Printed)))),
+                _ => unsupported(condition, "While loops with a condition aren't supported by the SCGT parser yet"),
+            },
+
+            StmtKind::Return(value) if position == Position::Returned => match value {
+                Some(value) => self.expr(value),
+                None => Ok(new_expr(ExprKind::Constant(Constant::Null))),
+            },
+            StmtKind::Return(_) => Err(Unsupported { span: stmt.span.clone(), reason: "Returning before the end of a macro isn't supported by SCGT" }),
+
+            StmtKind::Expr(node) => {
+                let expr = self.expr(node)?;
+                match expr.kind {
+                    // values are printed anyway
+                    ExprKind::Print(value) if position == Position::Printed && is_implicitly_printed(&value) => Ok(*value),
+                    _ if position == Position::Printed && is_implicitly_printed(&expr) => {
+                        unsupported(node, "This value would be printed in SCGT")
+                    }
+                    _ => Ok(expr),
+                }
+            }
+
+            StmtKind::Unsupported => Err(Unsupported { span: stmt.span.clone(), reason: "This isn't part of the SPWN subset that can be converted" }),
+        }
+    }
+
+    fn expr(&mut self, node: &Node) -> Conversion<Expr> {
+        let kind = match &node.kind {
+            Spwn::Number(n) => ExprKind::Number(n.clone()),
+            Spwn::String(s) if s.is_empty() => ExprKind::Constant(Constant::EmptyString),
+            Spwn::String(s) => ExprKind::String(s.clone()),
+            Spwn::Bool(true) => ExprKind::Constant(Constant::True),
+            Spwn::Bool(false) => ExprKind::Constant(Constant::False),
+            Spwn::Null => ExprKind::Constant(Constant::Null),
+            Spwn::UnusedId(id) => ExprKind::Constant(match id {
+                'g' => Constant::Group,
+                'c' => Constant::Color,
+                'b' => Constant::Block,
+                _ => Constant::Item,
+            }),
+            Spwn::TypeIndicator(name) => ExprKind::TypeIndicator(name.clone()),
+            Spwn::Ident(name) => ExprKind::Ident(name.clone()),
+            Spwn::Builtins => return unsupported(node, "Built-in functions other than `$.print` aren't supported by SCGT"),
+            Spwn::EmptyArray => ExprKind::Constant(Constant::EmptyArray),
+
+            Spwn::Unary(value) => ExprKind::Invert(Box::new(self.expr(value)?)),
+            Spwn::Binary(left, "*", right) => match (&left.kind, &right.kind) {
+                (Spwn::Number(n), _) => ExprKind::ShortMultiplication(n.clone(), Box::new(self.expr(right)?)),
+                (_, Spwn::Number(n)) => ExprKind::ShortMultiplication(n.clone(), Box::new(self.expr(left)?)),
+                _ => return unsupported(node, "Only multiplication by a number is supported by the SCGT parser yet"),
+            },
+            Spwn::Binary(..) => return unsupported(node, "Operators aren't supported by the SCGT parser yet"),
+            Spwn::Member(value, _) if matches!(value.kind, Spwn::Builtins) => {
+                return unsupported(node, "Built-in functions other than `$.print` aren't supported by SCGT");
+            }
+            Spwn::Member(value, name) => ExprKind::MemberAccess { value: Box::new(self.expr(value)?), name: name.clone() },
+            Spwn::Call(callee, args) => return self.call(node, callee, args),
+            Spwn::TriggerCall(value) => call_no_args(self.expr(value)?),
+
+            Spwn::Macro { params, body } => match params.as_slice() {
+                [] => ExprKind::Macro { params: Vec::new(), body: self.macro_body(body) },
+                [param] if param == "x" => ExprKind::Macro { params: params.clone(), body: self.macro_body(body) },
+                _ => return unsupported(node, "Macros with arguments other than a single `x` aren't supported by the SCGT parser yet"),
+            },
+            Spwn::TriggerFunction(body) => ExprKind::TriggerFunction(self.stmts(body, Position::Printed)),
+
+            Spwn::Unsupported(reason) => return unsupported(node, reason),
+        };
+
+        Ok(new_expr(kind))
+    }
+
+    fn call(&mut self, node: &Node, callee: &Node, args: &[Node]) -> Conversion<Expr> {
+        let kind = match (&callee.kind, args) {
+            (Spwn::Member(object, name), [value]) if matches!(object.kind, Spwn::Builtins) && name == "print" => {
+                ExprKind::Print(Box::new(self.expr(value)?))
+            }
+            (Spwn::Member(object, name), _) if matches!(object.kind, Spwn::Builtins) && name == "add" => {
+                return unsupported(node, "Objects aren't supported by the SCGT parser yet");
+            }
+            (Spwn::Ident(name), [event, value]) if name == "on" && is_touch(event) => {
+                ExprKind::OnTouch(Box::new(self.expr(value)?))
+            }
+            (Spwn::Macro { params, body }, []) if params.is_empty() => ExprKind::Block(self.macro_body(body)),
+            (_, []) => call_no_args(self.expr(callee)?),
+            _ => return unsupported(node, "Macro calls with arguments aren't supported by the SCGT parser yet"),
+        };
+
+        Ok(new_expr(kind))
+    }
+}
+
+fn call_no_args(callee: Expr) -> ExprKind {
+    match callee.kind {
+        // `fM` would define a macro named `f`
+        ExprKind::Ident(_) => ExprKind::MacroCallNoArgs(Box::new(new_expr(ExprKind::Block(vec![callee])))),
+        _ => ExprKind::MacroCallNoArgs(Box::new(callee)),
+    }
+}
+
+/// Whether the value is `touch()`.
+fn is_touch(node: &Node) -> bool {
+    matches!(&node.kind, Spwn::Call(callee, args) if args.is_empty() && matches!(&callee.kind, Spwn::Ident(name) if name == "touch"))
+}
+
+/// Golfs the statements converted since the last comment.
+fn flush(pending: &mut Vec<Expr>, lines: &mut Vec<String>) -> Result<(), String> {
+    if !pending.is_empty() {
+        let printed = format::print(pending, Encoding::default())?;
+        lines.push(golf::golf(pending, &printed)?.code);
+        pending.clear();
+    }
+    Ok(())
+}
+
+/// SCGT code converted from SPWN code, with warnings for every part that had to be left as a comment.
+pub struct Converted {
+    pub code: String,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Converts SPWN code to the shortest SCGT code that [`golf`] finds.
+///
+/// Statements that can't be converted are written as `//` comments before the top-level statement they belong to.
+/// SCGT has no comments, so these have to be ported or removed by hand before the code compiles.
+pub fn from_spwn(source: &str) -> Result<Converted, String> {
+    let tokens = tokenize(source);
+    let stmts = Parser { tokens: &tokens, pos: 0 }.stmts(None);
+
+    let mut converter = Converter::default();
+    let mut lines = Vec::new();
+    let mut pending = Vec::new();
+
+    for stmt in &stmts {
+        let before = converter.skipped.len();
+        let converted = converter.stmt(stmt, Position::Printed).and_then(|expr| {
+            match format::print(std::slice::from_ref(&expr), Encoding::default()) {
+                Ok(_) => Ok(expr),
+                Err(_) => Err(Unsupported { span: stmt.span.clone(), reason: "This can't be written in SCGT" }),
+            }
+        });
+
+        let converted = match converted {
+            Ok(expr) => Some(expr),
+            Err(cause) => {
+                // the whole statement is commented out, including the parts that were already skipped
+                converter.skipped.truncate(before);
+                converter.skipped.push(Skipped { stmt: stmt.span.clone(), cause });
+                None
+            }
+        };
+
+        if converter.skipped.len() > before {
+            flush(&mut pending, &mut lines)?;
+            for skipped in &converter.skipped[before..] {
+                lines.extend(source[skipped.stmt.clone()].lines().map(|line| format!("// {line}")));
+            }
+        }
+        pending.extend(converted);
+    }
+    flush(&mut pending, &mut lines)?;
+
+    let warnings = converter.skipped
+        .into_iter()
+        .map(|Skipped { stmt, cause }| {
+            let mut labels = vec![(cause.span.clone(), String::from("can't be converted"))];
+            if stmt != cause.span {
+                labels.push((stmt, String::from("so this statement is written as a comment")));
+            }
+
+            Diagnostic {
+                severity: Severity::Warning,
+                message: cause.reason.to_string(),
+                span: cause.span,
+                labels,
+                notes: vec![String::from("SCGT has no comments, so commented statements have to be ported by hand")],
+            }
+        })
+        .collect();
+
+    Ok(Converted { code: lines.join("\n"), warnings })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn supported() {
+        let converted = from_spwn(r#"
+            let a = 5 // five
+            $.print(a)
+            $.print("hi")
+            let f = (x) {
+                $.print(2 * x)
+                return ()
+            }
+            f()
+            while true {
+                a.b = -a
+                on(touch(), !{ $.print(true) })
+            }
+        "#).unwrap();
+
+        assert!(converted.warnings.is_empty());
+        assert_eq!(converted.code, "a!5;$a`hi`fX2xN;(f;MLa.b!!a;E}T");
+    }
+
+    #[test]
+    fn macros_without_return() {
+        let converted = from_spwn("let f = () { $.print(3) }\nlet v = f()\n$.print(v)").unwrap();
+        assert_eq!(converted.code, "fM3N;v!(f;M;$v");
+
+        let converted = from_spwn("let g = (x) { let a = 5 }\n$.print(() { let b = 2 } ())").unwrap();
+        assert_eq!(converted.code, "gXa!5;N;(b!2;N");
+        assert!(converted.warnings.is_empty());
+    }
+
+    #[test]
+    fn unsupported() {
+        let source = "let a = [1, 2]\n$.add(obj {\n    X: 1,\n})\nlet f = () {\n    if a { }\n    $.print(a)\n}\n$.print({b: 1})";
+        let converted = from_spwn(source).unwrap();
+
+        assert_eq!(converted.code, "// let a = [1, 2]\n// $.add(obj {\n//     X: 1,\n// })\n// if a { }\nfM$aN\n// $.print({b: 1})");
+        assert_eq!(converted.warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>(), [
+            "Arrays with elements aren't supported by the SCGT parser yet",
+            "Objects aren't supported by the SCGT parser yet",
+            "This isn't part of the SPWN subset that can be converted",
+            "Dictionaries aren't supported by the SCGT parser yet",
+        ]);
+    }
+}
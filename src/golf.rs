@@ -0,0 +1,188 @@
+//! Searching for shorter SCGT code that generates the same SPWN code, for `golf`.
+
+use clap::ValueEnum;
+
+use crate::ast::{Expr, ExprKind};
+use crate::format::{self, Encoding};
+use crate::style::{Comments, Style};
+use crate::{fold, Options};
+
+/// A way of shortening code.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rewrite {
+    /// Leaving out `;`, spaces, newlines and closing backticks that aren't needed.
+    Closings,
+    /// `'c` instead of `` `c` ``.
+    CharLiterals,
+    /// `aMb` and `aXb` instead of assigning macros with `!`.
+    NamedMacros,
+    /// `2`ab` instead of `` `abab` ``.
+    ShortMultiplication,
+    /// Evaluating operations on literals, such as `6` instead of `2 3`.
+    Folding,
+}
+
+impl Rewrite {
+    pub const ALL: [Self; 5] = [Self::Closings, Self::CharLiterals, Self::NamedMacros, Self::ShortMultiplication, Self::Folding];
+}
+
+pub struct Golfed {
+    pub code: String,
+    /// Bytes that each rewrite saves compared to applying every other rewrite.
+    pub savings: Vec<(Rewrite, usize)>,
+}
+
+/// Finds the shortest code this module can produce which generates the same SPWN code as the source.
+pub fn golf(stmts: &[Expr], source: &str) -> Result<Golfed, String> {
+    let expected = spwn(source);
+    let golfed = apply(stmts, &Rewrite::ALL, &expected)?;
+
+    if golfed.len() >= source.len() {
+        return Ok(Golfed { code: source.to_string(), savings: Vec::new() });
+    }
+
+    if spwn(&golfed) != expected {
+        return Err(String::from("Golfing changed the generated code, please report this as a bug"));
+    }
+
+    let savings = Rewrite::ALL
+        .into_iter()
+        .filter_map(|rewrite| {
+            let others = Rewrite::ALL.into_iter().filter(|&other| other != rewrite).collect::<Vec<_>>();
+            let without = apply(stmts, &others, &expected).ok()?;
+            let saved = without.len().saturating_sub(golfed.len());
+            (saved > 0).then_some((rewrite, saved))
+        })
+        .collect();
+
+    Ok(Golfed { code: golfed, savings })
+}
+
+/// Generated SPWN code, without comments since those refer to the source.
+fn spwn(code: &str) -> Option<String> {
+    let options = Options {
+        style: Style { comments: Comments::None, ..Style::default() },
+        ..Options::default()
+    };
+    crate::compile(code, &options).code
+}
+
+fn apply(stmts: &[Expr], rewrites: &[Rewrite], expected: &Option<String>) -> Result<String, String> {
+    let encoding = Encoding {
+        char_literals: rewrites.contains(&Rewrite::CharLiterals),
+        named_macros: rewrites.contains(&Rewrite::NamedMacros),
+    };
+
+    let mut stmts = stmts.to_vec();
+    if rewrites.contains(&Rewrite::Folding) {
+        stmts = rewrite_each(stmts, fold_literals, encoding, expected);
+    }
+    if rewrites.contains(&Rewrite::ShortMultiplication) {
+        stmts = rewrite_each(stmts, repeated_string, encoding, expected);
+    }
+
+    if rewrites.contains(&Rewrite::Closings) {
+        format::golf_with(&stmts, encoding)
+    } else {
+        format::print(&stmts, encoding)
+    }
+}
+
+/// Tries the rewrite on every expression, keeping it where the code gets shorter and generates the same SPWN code.
+fn rewrite_each(mut stmts: Vec<Expr>, rewrite: fn(&mut Expr) -> bool, encoding: Encoding, expected: &Option<String>) -> Vec<Expr> {
+    let Ok(mut len) = format::print(&stmts, encoding).map(|code| code.len()) else { return stmts };
+
+    for index in 0.. {
+        let mut candidate = stmts.clone();
+        match rewrite_nth(&mut candidate, index, rewrite) {
+            None => break,
+            Some(false) => continue,
+            Some(true) => (),
+        }
+
+        if let Ok(code) = format::print(&candidate, encoding) {
+            if code.len() < len && spwn(&code) == *expected {
+                len = code.len();
+                stmts = candidate;
+            }
+        }
+    }
+
+    stmts
+}
+
+/// Applies the rewrite to the expression at a preorder index,
+/// returning whether it changed anything or `None` if there are fewer expressions.
+fn rewrite_nth(stmts: &mut [Expr], index: usize, rewrite: fn(&mut Expr) -> bool) -> Option<bool> {
+    fn visit(expr: &mut Expr, index: &mut usize, rewrite: fn(&mut Expr) -> bool) -> Option<bool> {
+        if *index == 0 {
+            return Some(rewrite(expr));
+        }
+        *index -= 1;
+
+        let mut result = None;
+        expr.for_each_child_mut(|child| {
+            if result.is_none() {
+                result = visit(child, index, rewrite);
+            }
+        });
+        result
+    }
+
+    let mut index = index;
+    stmts.iter_mut().find_map(|stmt| visit(stmt, &mut index, rewrite))
+}
+
+fn fold_literals(expr: &mut Expr) -> bool {
+    let before = expr.clone();
+    fold::fold_expr(expr);
+    !expr.same_as(&before)
+}
+
+/// Writes a string made of a repeated part as a short multiplication.
+fn repeated_string(expr: &mut Expr) -> bool {
+    let ExprKind::String(s) = &expr.kind else { return false };
+    let chars = s.chars().collect::<Vec<_>>();
+
+    let period = (1..chars.len()).find(|&period| {
+        chars.len() % period == 0 && chars.chunks(period).all(|chunk| chunk == &chars[..period])
+    });
+
+    let Some(period) = period else { return false };
+    let part = Expr::new(ExprKind::String(chars[..period].iter().collect()), expr.span);
+    expr.kind = ExprKind::ShortMultiplication((chars.len() / period).to_string(), Box::new(part));
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn golf_code(code: &str) -> Golfed {
+        let stmts = crate::parser::parse_test_code(code);
+        golf(&stmts, code).unwrap()
+    }
+
+    #[test]
+    fn rewrites() {
+        let golfed = golf_code("a!M$`x`;;\n$(2 3;\n$`abab`");
+        assert_eq!(golfed.code, "aM$'x;$6\n$2`ab");
+        assert_eq!(golfed.savings, [
+            (Rewrite::Closings, 2),
+            (Rewrite::CharLiterals, 1),
+            (Rewrite::NamedMacros, 2),
+            (Rewrite::ShortMultiplication, 1),
+            (Rewrite::Folding, 3),
+        ]);
+    }
+
+    #[test]
+    fn same_spwn() {
+        // `10'a` would generate `10 * "a"` instead of the string itself
+        let golfed = golf_code("$`aaaaaaaaaa`\n$!3");
+        assert_eq!(golfed.code, "$`aaaaaaaaaa`$!3");
+        assert_eq!(spwn(&golfed.code), spwn("$`aaaaaaaaaa`\n$!3"));
+
+        assert!(golf_code("$1").savings.is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! Compiler from SCGT, a golfing language, to SPWN code.
+//!
+//! [`compile`] covers compiling a single file. The binary also shares helpers between files.
+
+use std::ops::Range;
+
+use clap::ValueEnum;
+
+use codegen::Generated;
+use util::helpers::{HelperImpls, DEFAULT_PREFIX};
+
+#[allow(dead_code)] // TODO remove when finish RWRT
+mod lex;
+#[allow(dead_code)]
+mod parse;
+
+mod ast;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+mod codegen;
+mod config;
+mod explain;
+mod format;
+mod fold;
+mod from_spwn;
+mod golf;
+#[cfg(all(feature = "lsp", not(target_arch = "wasm32")))]
+mod lsp;
+mod map_error;
+mod parser;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))] // only called from JavaScript
+mod playground;
+mod stats;
+mod style;
+mod target;
+mod types;
+mod util;
+mod warnings;
+
+pub use style::{BraceStyle, Comments, Indent, Style};
+pub use target::Target;
+pub use util::diagnostics::{Diagnostic, Severity};
+pub use util::source_map::{Mapping, SourceMap};
+pub use warnings::{Lint, LintLevels};
+
+// entry points of the binaries, which are built on internals that aren't part of the API
+#[cfg(not(target_arch = "wasm32"))]
+#[doc(hidden)]
+pub use cli::run as run_cli;
+#[cfg(all(feature = "lsp", not(target_arch = "wasm32")))]
+#[doc(hidden)]
+pub use lsp::serve_stdio;
+
+/// Name of the module that helpers are imported from with [`HelpersMode::Import`].
+pub const HELPERS_MODULE: &str = "scgt_helpers.spwn";
+
+/// Where generated code gets the helper functions it uses from.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HelpersMode {
+    /// Define the helpers used at the start of each output file.
+    Inline,
+    /// Import the helpers from a shared module written to scgt_helpers.spwn.
+    Import,
+}
+
+/// Settings for compiling SCGT code.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Formatting of the generated code, including indentation and comments.
+    pub style: Style,
+    pub target: Target,
+    /// Prefix of generated identifiers.
+    pub prefix: String,
+    pub helpers_mode: HelpersMode,
+    pub lints: LintLevels,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            style: Style::default(),
+            target: Target::V0_8,
+            prefix: DEFAULT_PREFIX.to_string(),
+            helpers_mode: HelpersMode::Inline,
+            lints: LintLevels::default(),
+        }
+    }
+}
+
+impl Options {
+    /// The options relevant to code generation.
+    pub(crate) fn codegen(&self) -> codegen::Options {
+        codegen::Options {
+            style: self.style.clone(),
+            target: self.target,
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+/// Result of compiling a single file.
+pub struct CompileOutput {
+    /// Generated SPWN code, unless the source couldn't be parsed at all.
+    pub code: Option<String>,
+    /// Errors and warnings, with spans referring to the source.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Ranges of the generated code and the source they were generated from.
+    pub mappings: Vec<Mapping>,
+    /// Contents of [`HELPERS_MODULE`] with [`HelpersMode::Import`] if the code uses any helpers.
+    pub helpers_module: Option<String>,
+}
+
+impl CompileOutput {
+    /// Source map for the generated code, given the paths of the generated and source files.
+    pub fn source_map(&self, file: &str, source_file: &str) -> Option<SourceMap> {
+        let code = self.code.as_ref()?;
+        Some(SourceMap::new(file, source_file, code, &self.mappings))
+    }
+}
+
+/// Parses and checks SCGT code, generating SPWN code without helpers if possible.
+///
+/// Newlines in `source` should already be normalized to `\n`.
+pub(crate) fn generate(source: &str, options: &Options) -> (Option<Generated>, Vec<Diagnostic>) {
+    let (ast, errors) = parser::parse(source).into_output_errors();
+
+    let mut diagnostics = errors
+        .iter()
+        .map(Diagnostic::from_rich)
+        .collect::<Vec<_>>();
+
+    let generated = ast.map(|mut ast| {
+        diagnostics.extend(warnings::check(&ast, &options.lints, &options.prefix));
+        fold::fold(&mut ast);
+        codegen::generate(&ast, source, &options.codegen())
+    });
+
+    (generated, diagnostics)
+}
+
+/// Compiles SCGT code to SPWN code using the built-in helpers.
+pub fn compile(source: &str, options: &Options) -> CompileOutput {
+    compile_with_helpers(source, options, &HelperImpls::builtin(options.target))
+}
+
+/// Compiles SCGT code to SPWN code using the given helper implementations.
+pub(crate) fn compile_with_helpers(source: &str, options: &Options, impls: &HelperImpls) -> CompileOutput {
+    let mut output = compile_normalized(&source.replace("\r\n", "\n"), options, impls);
+
+    // spans refer to the normalized source, but should refer to the source as it was given
+    restore_diagnostic_spans(source, &mut output.diagnostics);
+    restore_mapping_spans(source, &mut output.mappings);
+    output
+}
+
+/// Converts the spans of diagnostics about `source` with `\r\n` normalized to `\n` to spans of `source` itself.
+pub(crate) fn restore_diagnostic_spans(source: &str, diagnostics: &mut [Diagnostic]) {
+    let span = original_span(source);
+    for diagnostic in diagnostics {
+        diagnostic.span = span(&diagnostic.span);
+        for (label_span, _) in &mut diagnostic.labels {
+            *label_span = span(label_span);
+        }
+    }
+}
+
+/// Converts the source spans of mappings from `source` with `\r\n` normalized to `\n` to spans of `source` itself.
+pub(crate) fn restore_mapping_spans(source: &str, mappings: &mut [Mapping]) {
+    let span = original_span(source);
+    for mapping in mappings {
+        mapping.source = span(&mapping.source);
+    }
+}
+
+/// Converts spans of `source` with `\r\n` normalized to `\n` to spans of `source` itself.
+fn original_span(source: &str) -> impl Fn(&Range<usize>) -> Range<usize> {
+    // offsets into the normalized source where a `\r` was removed
+    let removed = source
+        .match_indices("\r\n")
+        .enumerate()
+        .map(|(i, (offset, _))| offset - i)
+        .collect::<Vec<_>>();
+
+    move |span| {
+        let offset = |offset: usize| offset + removed.partition_point(|&removed| removed < offset);
+        offset(span.start)..offset(span.end)
+    }
+}
+
+fn compile_normalized(source: &str, options: &Options, impls: &HelperImpls) -> CompileOutput {
+    let (generated, mut diagnostics) = generate(source, options);
+
+    let Some(generated) = generated else {
+        return CompileOutput { code: None, diagnostics, mappings: Vec::new(), helpers_module: None };
+    };
+
+    let (output, helpers_module) = match options.helpers_mode {
+        HelpersMode::Inline => (generated.with_helpers(impls), None),
+        HelpersMode::Import => (
+            generated.with_import(HELPERS_MODULE),
+            (!generated.helpers.is_empty()).then(|| codegen::helpers_module([&generated], impls, &options.codegen())),
+        ),
+    };
+
+    let output = match output {
+        Ok(output) => output,
+        Err(message) => {
+            diagnostics.push(Diagnostic { severity: Severity::Error, message, span: 0..0, labels: Vec::new(), notes: Vec::new() });
+            return CompileOutput { code: None, diagnostics, mappings: Vec::new(), helpers_module: None };
+        }
+    };
+
+    CompileOutput {
+        code: Some(output.code),
+        diagnostics,
+        mappings: output.mappings,
+        helpers_module,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn marker_characters() {
+        let output = compile("$`a\u{FDD0}\u{FDD2}b", &Options::default());
+        assert_eq!(output.code.as_deref(), Some("// $`a\\u{FDD0}\\u{FDD2}b\n$.print(\"a\\u{FDD0}\\u{FDD2}b\")\n"));
+        assert!(output.diagnostics.is_empty());
+        assert_eq!(output.mappings.len(), 1);
+    }
+
+    #[test]
+    fn compile_inline() {
+        let output = compile("a!2;\r\n$a", &Options::default());
+        assert_eq!(output.code.as_deref(), Some("// a!2;\nlet a = 2\n\n// $a\n$.print(a)\n"));
+        assert!(output.diagnostics.is_empty());
+        assert!(output.helpers_module.is_none());
+
+        let map = output.source_map("out.spwn", "in.scgt").unwrap();
+        assert_eq!(map.source_span(2, 1), Some(0..4));
+        assert_eq!(map.source_span(5, 1), Some(6..8));
+
+        let output = compile("a!2;\r\n\r\n$b", &Options::default());
+        let spans = output.diagnostics.iter().map(|diagnostic| diagnostic.span.clone()).collect::<Vec<_>>();
+        assert_eq!(spans, [0..1, 9..10]);
+    }
+
+    #[test]
+    fn compile_import() {
+        let options = Options { helpers_mode: HelpersMode::Import, ..Options::default() };
+        let output = compile("$(b!2;$b", &options);
+        assert!(output.code.unwrap().starts_with("let {_scgt_print} = import \"scgt_helpers.spwn\"\n"));
+        assert!(output.helpers_module.unwrap().contains("_scgt_print: _scgt_print,"));
+    }
+
+    #[test]
+    fn compile_errors() {
+        let output = compile("a!", &Options::default());
+        assert!(output.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error));
+    }
+}
@@ -0,0 +1,383 @@
+//! Language server for SCGT files, run by the `scgt-lsp` binary.
+//!
+//! Besides diagnostics, hovering over code shows the SPWN code generated for the innermost statement under the cursor,
+//! and the [`SHOW_GENERATED_COMMAND`] command returns the generated code of a whole document.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use serde_json::Value;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::*;
+use tower_lsp::{Client, LanguageServer, LspService, Server};
+
+use crate::config::Config;
+use crate::target::Target;
+use crate::util::diagnostics::{self, Severity};
+use crate::util::helpers::HelperImpls;
+use crate::util::source_map::Mapping;
+use crate::{CompileOutput, Options};
+
+/// Command that takes the URI of an open document and returns the SPWN code generated for it.
+pub const SHOW_GENERATED_COMMAND: &str = "scgt.showGeneratedSpwn";
+
+pub struct Backend {
+    client: Client,
+    options: Options,
+    impls: HelperImpls,
+    /// Why the configuration file couldn't be loaded, which is shown once the client is initialized.
+    config_error: Option<String>,
+    /// Contents of open documents, with newlines normalized.
+    documents: Mutex<HashMap<Url, String>>,
+}
+
+impl Backend {
+    pub fn new(client: Client) -> Self {
+        let (options, impls, config_error) = match load_config() {
+            Ok((options, impls)) => (options, impls, None),
+            Err(err) => {
+                let options = Options::default();
+                let impls = HelperImpls::builtin(options.target);
+                (options, impls, Some(err))
+            }
+        };
+
+        Self { client, options, impls, config_error, documents: Mutex::default() }
+    }
+
+    fn compile(&self, uri: &Url) -> Option<(String, CompileOutput)> {
+        let source = self.documents.lock().unwrap().get(uri)?.clone();
+        let output = crate::compile_with_helpers(&source, &self.options, &self.impls);
+        Some((source, output))
+    }
+
+    async fn update(&self, uri: Url, text: String, version: i32) {
+        self.documents.lock().unwrap().insert(uri.clone(), text.replace("\r\n", "\n"));
+
+        let Some((source, output)) = self.compile(&uri) else { return };
+        let diagnostics = output.diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let mut message = diagnostic.message.clone();
+                if let Some((_, label)) = diagnostic.labels.first().filter(|(_, label)| *label != message) {
+                    message = format!("{message}\n{label}");
+                }
+                for note in &diagnostic.notes {
+                    message = format!("{message}\n{note}");
+                }
+
+                Diagnostic {
+                    range: range(&source, &diagnostic.span),
+                    severity: Some(match diagnostic.severity {
+                        Severity::Error => DiagnosticSeverity::ERROR,
+                        Severity::Warning => DiagnosticSeverity::WARNING,
+                    }),
+                    source: Some(String::from("scgt")),
+                    message,
+                    ..Diagnostic::default()
+                }
+            })
+            .collect();
+
+        self.client.publish_diagnostics(uri, diagnostics, Some(version)).await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+        Ok(InitializeResult {
+            server_info: Some(ServerInfo {
+                name: String::from("scgt-lsp"),
+                version: Some(String::from(env!("CARGO_PKG_VERSION"))),
+            }),
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![String::from(SHOW_GENERATED_COMMAND)],
+                    ..ExecuteCommandOptions::default()
+                }),
+                ..ServerCapabilities::default()
+            },
+        })
+    }
+
+    async fn initialized(&self, _: InitializedParams) {
+        if let Some(err) = &self.config_error {
+            self.client.show_message(MessageType::ERROR, err).await;
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.update(document.uri, document.text, document.version).await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        // only full syncs are supported, so the last change has the whole document
+        if let Some(change) = params.content_changes.into_iter().last() {
+            let document = params.text_document;
+            self.update(document.uri, change.text, document.version).await;
+        }
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.lock().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position = params.text_document_position_params;
+        let Some((source, output)) = self.compile(&position.text_document.uri) else { return Ok(None) };
+        let Some(code) = &output.code else { return Ok(None) };
+
+        let Some(mapping) = innermost_mapping(&output.mappings, offset(&source, position.position)) else { return Ok(None) };
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```spwn\n{}\n```", &code[mapping.generated.clone()]),
+            }),
+            range: Some(range(&source, &mapping.source)),
+        }))
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        if params.command != SHOW_GENERATED_COMMAND {
+            return Err(Error::invalid_params(format!("Unknown command {:?}", params.command)));
+        }
+
+        let uri = params.arguments
+            .first()
+            .and_then(Value::as_str)
+            .and_then(|uri| Url::parse(uri).ok())
+            .ok_or_else(|| Error::invalid_params("Expected the URI of a document"))?;
+
+        let (_, output) = self.compile(&uri).ok_or_else(|| Error::invalid_params(format!("{uri} is not open")))?;
+        Ok(output.code.map(Value::String))
+    }
+}
+
+/// Runs the language server over stdin and stdout until the client exits.
+pub async fn serve_stdio() {
+    let (service, socket) = LspService::new(Backend::new);
+    Server::new(tokio::io::stdin(), tokio::io::stdout(), socket).serve(service).await;
+}
+
+/// Mapping with the smallest source span that contains a byte offset, which is the innermost statement there.
+fn innermost_mapping(mappings: &[Mapping], offset: usize) -> Option<&Mapping> {
+    mappings
+        .iter()
+        .filter(|mapping| mapping.source.contains(&offset))
+        .min_by_key(|mapping| mapping.source.len())
+}
+
+/// Options and helper implementations from the configuration file, like the compiler uses without arguments.
+fn load_config() -> std::result::Result<(Options, HelperImpls), String> {
+    let config = Config::load()?;
+    let target = config.target.unwrap_or(Target::V0_8);
+
+    let impls = match &config.helpers_dir {
+        Some(dir) => HelperImpls::load(target, dir).map_err(|err| err.to_string())?,
+        None => HelperImpls::builtin(target),
+    };
+
+    let options = Options {
+        style: config.style.style(),
+        target,
+        prefix: config.prefix()?,
+        ..Options::default()
+    };
+    Ok((options, impls))
+}
+
+/// Byte offset of an LSP position, which counts UTF-16 code units.
+fn offset(source: &str, position: Position) -> usize {
+    let line_start = source
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+
+    let mut units = 0;
+    source[line_start..]
+        .char_indices()
+        .find(|&(_, c)| {
+            units += c.len_utf16();
+            c == '\n' || units > position.character as usize
+        })
+        .map_or(source.len(), |(i, _)| line_start + i)
+}
+
+fn range(source: &str, span: &Range<usize>) -> tower_lsp::lsp_types::Range {
+    let position = |offset: usize| {
+        let (line, _) = diagnostics::line_column(source, offset);
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let character = source[line_start..offset].encode_utf16().count();
+        Position::new(line as u32 - 1, character as u32)
+    };
+
+    tower_lsp::lsp_types::Range::new(position(span.start), position(span.end))
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf};
+
+    use super::*;
+
+    /// Client that talks to a server running in the same process.
+    struct TestClient {
+        reader: BufReader<ReadHalf<DuplexStream>>,
+        writer: WriteHalf<DuplexStream>,
+        next_id: i64,
+    }
+
+    impl TestClient {
+        fn start() -> Self {
+            let (client, server) = tokio::io::duplex(1 << 16);
+            let (server_read, server_write) = tokio::io::split(server);
+            let (service, socket) = LspService::new(Backend::new);
+            tokio::spawn(Server::new(server_read, server_write, socket).serve(service));
+
+            let (reader, writer) = tokio::io::split(client);
+            Self { reader: BufReader::new(reader), writer, next_id: 0 }
+        }
+
+        async fn send(&mut self, message: Value) {
+            let body = message.to_string();
+            let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+            self.writer.write_all(frame.as_bytes()).await.unwrap();
+        }
+
+        async fn receive(&mut self) -> Value {
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).await.unwrap();
+                match line.trim_end().split_once(": ") {
+                    Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                    _ if line == "\r\n" => break,
+                    _ => (),
+                }
+            }
+
+            let mut body = vec![0; length];
+            self.reader.read_exact(&mut body).await.unwrap();
+            serde_json::from_slice(&body).unwrap()
+        }
+
+        async fn notify(&mut self, method: &str, params: Value) {
+            self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params })).await;
+        }
+
+        /// Sends a request and returns its result, skipping notifications sent in the meantime.
+        async fn request(&mut self, method: &str, params: Value) -> Value {
+            self.next_id += 1;
+            let id = self.next_id;
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })).await;
+
+            loop {
+                let message = self.receive().await;
+                if message["id"] == id {
+                    return message["result"].clone();
+                }
+            }
+        }
+
+        async fn notification(&mut self, method: &str) -> Value {
+            loop {
+                let message = self.receive().await;
+                if message["method"] == method {
+                    return message["params"].clone();
+                }
+            }
+        }
+
+        async fn open(&mut self, uri: &str, text: &str) -> Value {
+            let result = self.request("initialize", json!({ "capabilities": {} })).await;
+            self.notify("initialized", json!({})).await;
+            self.notify("textDocument/didOpen", json!({
+                "textDocument": { "uri": uri, "languageId": "scgt", "version": 1, "text": text },
+            })).await;
+            result
+        }
+    }
+
+    const URI: &str = "file:///test.scgt";
+
+    #[tokio::test]
+    async fn diagnostics_on_change() {
+        let mut client = TestClient::start();
+        let result = client.open(URI, "a!2;$a").await;
+        assert_eq!(result["capabilities"]["executeCommandProvider"]["commands"], json!([SHOW_GENERATED_COMMAND]));
+
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["diagnostics"], json!([]));
+
+        client.notify("textDocument/didChange", json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "$b" }],
+        })).await;
+
+        let params = client.notification("textDocument/publishDiagnostics").await;
+        assert_eq!(params["version"], 2);
+        let diagnostic = &params["diagnostics"][0];
+        assert_eq!(diagnostic["severity"], 2);
+        assert_eq!(diagnostic["range"], json!({
+            "start": { "line": 0, "character": 1 },
+            "end": { "line": 0, "character": 2 },
+        }));
+    }
+
+    #[tokio::test]
+    async fn hover_and_command() {
+        let mut client = TestClient::start();
+        client.open(URI, "a!2;\n$a").await;
+
+        let hover = client.request("textDocument/hover", json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 1, "character": 1 },
+        })).await;
+        assert_eq!(hover["contents"]["value"], "```spwn\n$.print(a)\n```");
+        assert_eq!(hover["range"], json!({
+            "start": { "line": 1, "character": 0 },
+            "end": { "line": 1, "character": 2 },
+        }));
+
+        let code = client.request("workspace/executeCommand", json!({
+            "command": SHOW_GENERATED_COMMAND,
+            "arguments": [URI],
+        })).await;
+        assert_eq!(code, "// a!2;\nlet a = 2\n\n// $a\n$.print(a)\n");
+
+        client.open(URI, "a!2'c;\n$a").await;
+        let hover = client.request("textDocument/hover", json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 0, "character": 2 },
+        })).await;
+        assert_eq!(hover["contents"]["value"], "```spwn\nlet a = \"cc\"\n```");
+        assert_eq!(hover["range"], json!({
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 0, "character": 6 },
+        }));
+    }
+
+    #[test]
+    fn positions() {
+        let source = "ab\né𝄞c\n";
+        assert_eq!(offset(source, Position::new(1, 0)), 3);
+        assert_eq!(offset(source, Position::new(1, 3)), 9);
+        assert_eq!(offset(source, Position::new(1, 10)), 10);
+        assert_eq!(offset(source, Position::new(5, 0)), source.len());
+        assert_eq!(range(source, &(5..10)), tower_lsp::lsp_types::Range::new(Position::new(1, 1), Position::new(1, 4)));
+    }
+}
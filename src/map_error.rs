@@ -0,0 +1,87 @@
+/// Location of an error reported by the SPWN compiler.
+#[derive(PartialEq, Eq, Debug)]
+pub struct SpwnError {
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Finds the message and the first `<file>.spwn:<line>:<column>` location in SPWN compiler output.
+pub fn parse(output: &str) -> Option<SpwnError> {
+    let output = strip_ansi(output);
+
+    let (file, line, column) = output.match_indices(".spwn:").find_map(|(i, _)| {
+        let is_path_char = |c: char| !c.is_whitespace() && !"[(<\"'".contains(c) && c.is_ascii();
+        let start = output[..i]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| !is_path_char(c))
+            .map_or(0, |(j, c)| j + c.len_utf8());
+        let end = i + ".spwn".len();
+
+        let (line, rest) = leading_number(&output[end + 1..])?;
+        let column = rest.strip_prefix(':').and_then(leading_number).map_or(1, |(column, _)| column);
+        Some((output[start..end].to_string(), line, column))
+    })?;
+
+    let message = output
+        .lines()
+        .find_map(|line| line.split_once("Error:").map(|(_, message)| message.trim()))
+        .or_else(|| output.lines().map(str::trim).find(|line| !line.is_empty()))
+        .unwrap_or_default()
+        .to_string();
+
+    Some(SpwnError { message, file, line, column })
+}
+
+/// Splits a number off the start of the text.
+fn leading_number(text: &str) -> Option<(usize, &str)> {
+    let len = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    text[..len].parse().ok().map(|n| (n, &text[len..]))
+}
+
+/// Removes terminal color codes.
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ariadne_output() {
+        let output = "\x1b[31mError:\x1b[0m Cannot find variable `x`\n   ╭─[/home/me/output.spwn:19:5]\n   │\n19 │ x\n";
+        assert_eq!(parse(output), Some(SpwnError {
+            message: String::from("Cannot find variable `x`"),
+            file: String::from("/home/me/output.spwn"),
+            line: 19,
+            column: 5,
+        }));
+    }
+
+    #[test]
+    fn plain_output() {
+        let output = "type mismatch\n  at output.spwn:3\n";
+        assert_eq!(parse(output), Some(SpwnError {
+            message: String::from("type mismatch"),
+            file: String::from("output.spwn"),
+            line: 3,
+            column: 1,
+        }));
+
+        assert_eq!(parse("Error: something went wrong"), None);
+    }
+}
@@ -1,13 +1,9 @@
-/// throughout this file '#' will be used as a placeholder for generated code to be inserted
-
-use std::collections::BTreeSet;
-
 use chumsky::prelude::*;
 
-use crate::util::helpers::*;
+use crate::ast::*;
 
 type Err<'a> = Rich<'a, char>;
-type Extra<'a> = extra::Full<Err<'a>, State<'a>, ()>;
+type Extra<'a> = extra::Err<Err<'a>>;
 
 macro_rules! parser_type {
     ( $lt:lifetime, $ret_t:ty ) => {
@@ -15,124 +11,14 @@ macro_rules! parser_type {
     }
 }
 
-struct State<'a> {
-    helpers: BTreeSet<HelperFunction>,
-    variables: BTreeSet<String>,
-    source: &'a str,
-
-    indent_size: usize,
-    
-    depth: usize,
+pub fn parse(code: &str) -> ParseResult<Vec<Expr>, Err<'_>> {
+    parser().parse(code)
 }
 
-impl<'a> State<'a> {
-    fn new(source: &'a str, indent_size: usize) -> Self {
-        Self {
-            helpers: BTreeSet::new(),
-            variables: BTreeSet::new(),
-            source,
-
-            indent_size,
-
-            depth: 1,
-        }
-    }
-
-    fn add_helper(&mut self, helper: HelperFunction) -> &'static str {
-        self.helpers.insert(helper);
-        helper.spwn_name()
-    }
-
-    fn get_indent(&self) -> String {
-        " ".repeat(self.indent_size)
-    }
-}
-
-enum PrintBehavior {
-    Implicit,
-    Explicit,
-}
-
-struct CodeVariables {
-    code: String,
-    helpers: Option<BTreeSet<HelperFunction>>,
-    variables: Option<BTreeSet<String>>,
-}
-
-impl CodeVariables {
-    fn none(code: String) -> Self {
-        Self {
-            code,
-            helpers: None,
-            variables: None,
-        }
-    }
-}
-
-struct SpwnCode {
-    expr: CodeVariables,
-    stmt: Option<CodeVariables>,
-    span: SimpleSpan,
-    print: PrintBehavior,
-}
-
-impl SpwnCode {
-    fn simple_implicit(code: CodeVariables, span: SimpleSpan) -> Self {
-        SpwnCode {
-            expr: code, stmt: None,
-            span,
-            print: PrintBehavior::Implicit,
-        }
-    }
-
-    fn simple_explicit(code: CodeVariables, span: SimpleSpan) -> Self {
-        SpwnCode {
-            expr: code, stmt: None,
-            span,
-            print: PrintBehavior::Explicit,
-        }
-    }
-
-    fn get_code(&self, inline: bool, state: &mut State) -> String {
-        let code = if inline {
-            &self.expr
-        } else {
-            self.stmt.as_ref().unwrap_or(&self.expr)
-        };
-
-        if let Some(helpers) = &code.helpers {
-            state.helpers.extend(helpers.iter());
-        }
-
-        if let Some(variables) = &code.variables {
-            state.variables.extend(variables.iter().cloned());
-        }
-
-        code.code.to_string()
-    }
-}
-
-pub fn parse(code: &str, indent_size: usize) -> ParseResult<String, Err<'_>> {
-    let mut state = State::new(code, indent_size);
-    parser().parse_with_state(code, &mut state)
-}
-
-fn parser<'a>() -> parser_type!('a, String) {
-    let global = recursive(|block| {
-        let block = empty()
-            .map_with_state(move |_, _, state: &mut State| {
-                state.depth += 1;
-            })
-            .then(block)
-            // set to previous state in ALL CASES
-            .map_with_state(|((), out), _, state: &mut State| {
-                state.depth -= 1;
-                out
-            })
-            .map_err_with_state(|err, _, state: &mut State| {
-                state.depth -= 1;
-                err
-            });
+fn parser<'a>() -> parser_type!('a, Vec<Expr>) {
+    recursive(|block| {
+        // identifiers and numbers may have one trailing space to separate them from the next one
+        let separator = just(' ').or_not();
 
         let ident = one_of("abcdefghijklmnopqrstuvwxyz")
             .repeated().at_least(1)
@@ -140,10 +26,9 @@ fn parser<'a>() -> parser_type!('a, String) {
             .or(
                 just('_')
                     .ignore_then(text::ident())
-                    // .filter(|name: &&str| !name.starts_with("_scgt_"))
-                    // could also validate with warning or something
                     .map(String::from)
             )
+            .then_ignore(separator)
             .labelled("identifier");
 
         let closing = choice((
@@ -154,58 +39,44 @@ fn parser<'a>() -> parser_type!('a, String) {
 
         let expression = recursive(|expression| {
             let value = recursive(|value| {
-                let int = text::int(10).map(String::from);
+                let int = text::int(10).map(String::from).then_ignore(separator);
 
                 let float = text::int(10).slice().or_not()
                     .then_ignore(just('.'))
                     .then(text::digits(10).slice().or_not())
                     .filter(|(bef, aft)| bef.as_ref().or(aft.as_ref()).is_some())
-                    .map(|(bef, aft)| format!("{}.{}", bef.unwrap_or("0"), aft.unwrap_or("0")));
-    
+                    .map(|(bef, aft)| format!("{}.{}", bef.unwrap_or("0"), aft.unwrap_or("0")))
+                    .then_ignore(separator);
+
                 let short_multiplication = int.or(float)
                     .then_ignore(none_of("ABCDEFGLMNOSWX").rewind())
                     .then(value.clone())
-                    .map_with_state(|(n, code): (String, SpwnCode), _, state: &mut State| {
-                        let helper = state.add_helper(HelperFunction::Mul);
-                        format!("{helper}({n}, {})", code.get_code(true, state))
-                    });
-    
+                    .map(|(n, expr): (String, Expr)| ExprKind::ShortMultiplication(n, Box::new(expr)));
+
                 let char_literal = just('\'')
                     .ignore_then(any())
-                    .map(|c: char| format!("\"{c}\""));
-    
+                    .map(String::from);
+
                 let string_char = choice((
-                    // SCGT escapes
                     just('\\')
                         .ignore_then(
                             select! {
-                                'n' => "\\n",
-                                'r' => "\\r",
-                                't' => "\\t",
-                                '`' => "`",
-                                '\\' => "\\\\",
+                                'n' => '\n',
+                                'r' => '\r',
+                                't' => '\t',
+                                '`' => '`',
+                                '\\' => '\\',
                             }
-                        )
-                        .map(String::from),
-    
-                    // SPWN escapes
-                    select! {
-                        '"' => "\\\"",
-                        '\'' => "\\'",
-                        '\\' => "\\\\",
-                    }
-                    .map(String::from),
-    
-                    any()
-                        .and_is(just('`').ignored().or(text::newline()).not())
-                        .map(String::from)
+                        ),
+
+                    any().and_is(just('`').ignored().or(text::newline()).not())
                 ));
-    
+
                 let string = choice((
                     // `...`
                     string_char
                         .repeated()
-                        .collect::<Vec<_>>()
+                        .collect::<String>()
                         .delimited_by(
                             just('`'),
                             choice((
@@ -215,162 +86,102 @@ fn parser<'a>() -> parser_type!('a, String) {
                             ))
                         ),
                     // \...` (allows newlines)
-                    string_char.or(text::newline().to("\n".to_string()))
+                    string_char.or(text::newline().to('\n'))
                         .repeated()
-                        .collect::<Vec<_>>()
+                        .collect::<String>()
                         .delimited_by(just('\\'), just('`').ignored().or(end()))
-                ))
-                .map(|v: Vec<String>| format!("\"{}\"", v.into_iter().collect::<String>()));
-    
+                ));
+
                 let type_indicator = just('@')
-                    .ignore_then(ident)
-                    .map(|name: String| format!("@{name}"));
-    
+                    .ignore_then(ident);
+
                 let inner_block = block.clone()
                     .delimited_by(just('('), closing)
-                    .map_with_state(|stmts: Vec<SpwnCode>, _, state: &mut State| {
-                        let code = format_stmts(&stmts, state, false, Some("return #"));
-                        wrap_with_block(code, false, state)
-                    });
-    
+                    .map(ExprKind::Block);
+
                 let invert = just('!')
                     .ignore_then(expression.clone())
-                    .map_with_state(|code: SpwnCode, _, state: &mut State| {
-                        let helper = state.add_helper(HelperFunction::Invert);
-                        format!("{helper}({})", code.get_code(true, state))
-                    });
-    
+                    .map(|expr: Expr| ExprKind::Invert(Box::new(expr)));
+
                 let trigger_function = block.clone()
                     .delimited_by(just('}'), closing)
-                    .map_with_state(|stmts: Vec<SpwnCode>, _, state: &mut State| {
-                        // TODO check back here when `-> return`
-                        let code = format_stmts(&stmts, state, false, None);
-                        format!("!{{\n{code}\n}}")
-                    });
+                    .map(ExprKind::TriggerFunction);
 
                 let loop_variables = one_of("IJK")
-                    .map_with_state(|name: char, _, state: &mut State| {
-                        let name = format!("_scgt_loop_{}", name.to_lowercase());
-                        state.variables.insert(name.clone());
-                        name
-                    });
-    
+                    .map(ExprKind::LoopVariable);
+
                 let macro_def_no_args = block.clone()
                     .delimited_by(just('M'), closing)
-                    .map_with_state(|stmts: Vec<SpwnCode>, _, state: &mut State| {
-                        let code = format_stmts(&stmts, state, false, Some("return #"));
-                        format!("() {{\n{code}\n}}")
-                    });
-    
+                    .map(|body| ExprKind::Macro { params: Vec::new(), body })
+                    .map_with_span(Expr::new);
+
                 let macro_def_x_arg = block.clone()
                     .delimited_by(just('X'), closing)
-                    .map_with_state(|stmts: Vec<SpwnCode>, _, state: &mut State| {
-                        let code = format_stmts(&stmts, state, false, Some("return #"));
-                        format!("(x) {{\n{code}\n}}")
-                    });
+                    .map(|body| ExprKind::Macro { params: vec![String::from("x")], body })
+                    .map_with_span(Expr::new);
 
                 let hardcoded = select! {
-                    'A' => "[]",
-                    'B' => "?b",
-                    'C' => "?c",
-                    'D' => "?i",
-                    'F' => "false",
-                    'G' => "?g",
-                    'N' => "null",
-                    'S' => "\"\"",
-                    'T' => "true",
+                    'A' => Constant::EmptyArray,
+                    'B' => Constant::Block,
+                    'C' => Constant::Color,
+                    'D' => Constant::Item,
+                    'F' => Constant::False,
+                    'G' => Constant::Group,
+                    'N' => Constant::Null,
+                    'S' => Constant::EmptyString,
+                    'T' => Constant::True,
                 }
-                .map(String::from);
+                .map(ExprKind::Constant);
 
                 let implicit_print_values = choice((
                     short_multiplication,
-                    int, float,
-                    char_literal, string,
-                    type_indicator,
+                    int.map(ExprKind::Number), float.map(ExprKind::Number),
+                    char_literal.map(ExprKind::String), string.map(ExprKind::String),
+                    type_indicator.map(ExprKind::TypeIndicator),
                     inner_block,
                     invert,
                     loop_variables,
-                    macro_def_no_args.clone(), macro_def_x_arg.clone(), // named shortcuts in explicit print section
+                    // named shortcuts in explicit print section
+                    macro_def_no_args.clone().map(|expr| expr.kind),
+                    macro_def_x_arg.clone().map(|expr| expr.kind),
                     hardcoded,
                     trigger_function,
                 ))
-                // simple_implicit guarantees that the required variables and helper functions will be added
-                .map(CodeVariables::none)
-                .map_with_span(SpwnCode::simple_implicit);
-    
+                .map_with_span(Expr::new);
+
                 let explicit_print = just('$')
                     .ignore_then(expression.clone())
-                    .map_with_state(|code: SpwnCode, span, state: &mut State| {
-                        let code = code.get_code(true, state);
-
-                        let mut helpers = BTreeSet::new();
-                        let print = HelperFunction::Print;
-                        helpers.insert(print);
-
-                        SpwnCode {
-                            expr: CodeVariables {
-                                code: format!("{}({code})", print.spwn_name()),
-                                helpers: Some(helpers),
-                                variables: None,
-                            },
-                            stmt: Some(CodeVariables::none(format!("$.print({code})"))),
-                            span,
-                            print: PrintBehavior::Explicit,
-                        }
-                    });
-    
+                    .map(|expr: Expr| ExprKind::Print(Box::new(expr)));
+
                 let on_touch = expression.clone()
                     .delimited_by(just('E'), closing)
-                    .map_with_state(|code: SpwnCode, _, state: &mut State| {
-                        format!("on(touch(), {})", code.get_code(true, state))
-                    })
-                    .map(CodeVariables::none)
-                    .map_with_span(SpwnCode::simple_explicit);
-    
+                    .map(|expr: Expr| ExprKind::OnTouch(Box::new(expr)));
+
                 let infinite_loop = block.clone()
                     .delimited_by(just('L'), closing)
-                    .map_with_state(|stmts: Vec<SpwnCode>, span, state: &mut State| {
-                        format_loop("while true", &stmts, span, state)
-                    });
+                    .map(ExprKind::Loop);
 
-                let named_macro_no_args = ident.then(macro_def_no_args)
-                    .map_with_span(|(name, code): (String, String), span| {
-                        format_assign(name, code, span)
-                    });
+                let named_ident = ident
+                    .map_with_span(|name, span| Expr::new(ExprKind::Ident(name), span));
+
+                let named_macro_no_args = named_ident.then(macro_def_no_args);
+                let named_macro_x_arg = named_ident.then(macro_def_x_arg);
+
+                let value_ident = ident.map(ExprKind::Ident);
 
-                let named_macro_x_arg = ident.then(macro_def_x_arg)
-                    .map_with_span(|(name, code): (String, String), span| {
-                        format_assign(name, code, span)
-                    });
-    
-                let value_ident = ident
-                    .map_with_state(|name: String, span, state: &mut State| {
-                        state.variables.insert(name.clone());
-
-                        let mut helpers = BTreeSet::new();
-                        let get = HelperFunction::Get;
-                        helpers.insert(get);
-
-                        SpwnCode {
-                            expr: CodeVariables {
-                                code: format!("{}({name})", get.spwn_name()),
-                                helpers: Some(helpers),
-                                variables: None,
-                            },
-                            stmt: Some(CodeVariables::none(name)),
-                            span,
-                            print: PrintBehavior::Explicit,
-                        }
-                    });
-    
                 let atom = choice((
                     explicit_print,
                     on_touch,
                     infinite_loop,
-                    named_macro_no_args, named_macro_x_arg,
+                    named_macro_no_args.or(named_macro_x_arg)
+                        .map(|(target, value)| ExprKind::Assign {
+                            target: Box::new(target),
+                            value: Box::new(value),
+                        }),
                     value_ident,
-                    implicit_print_values,
-                ));
+                ))
+                .map_with_span(Expr::new)
+                .or(implicit_print_values);
 
                 struct Postfix {
                     span: SimpleSpan,
@@ -378,18 +189,16 @@ fn parser<'a>() -> parser_type!('a, String) {
                 }
 
                 enum PostfixType {
-                    Assignment { expr: String },
+                    Assignment { expr: Expr },
                     MemberAccess { name: String },
                     MacroCallNoArgs,
                 }
 
                 let assignment = expression.clone().delimited_by(just('!'), closing)
-                    .map_with_state(|code: SpwnCode, span, state: &mut State| {
+                    .map_with_span(|expr: Expr, span| {
                         Postfix {
                             span,
-                            data: PostfixType::Assignment {
-                                expr: code.get_code(true, state),
-                            },
+                            data: PostfixType::Assignment { expr },
                         }
                     });
 
@@ -410,40 +219,27 @@ fn parser<'a>() -> parser_type!('a, String) {
                         }
                     });
 
-                let postfixes = atom.foldl_with_state(
+                let postfixes = atom.foldl(
                     choice((
                         assignment,
                         member_access,
                         macro_call_no_args,
                     ))
                     .repeated(),
-                    |value: SpwnCode, postfix: Postfix, state: &mut State| {
-                        let code = value.get_code(false, state);
+                    |value: Expr, postfix: Postfix| {
                         let span = (value.span.start..postfix.span.end).into();
+                        let value = Box::new(value);
 
-                        match postfix.data {
-                            PostfixType::Assignment { expr } => format_assign(code, expr, span),
-                            
-                            PostfixType::MemberAccess { name } => SpwnCode::simple_implicit(
-                                CodeVariables::none(format!("{code}.{name}")),
-                                span,
-                            ),
-                            
-                            PostfixType::MacroCallNoArgs => {
-                                let mut helpers = BTreeSet::new();
-                                let call = HelperFunction::Call;
-                                helpers.insert(call);
-
-                                SpwnCode::simple_explicit(
-                                    CodeVariables {
-                                        code: format!("{}({code})", call.spwn_name()),
-                                        helpers: Some(helpers),
-                                        variables: None,
-                                    },
-                                    span,
-                                )
-                            }
-                        }
+                        let kind = match postfix.data {
+                            PostfixType::Assignment { expr } => ExprKind::Assign {
+                                target: value,
+                                value: Box::new(expr),
+                            },
+                            PostfixType::MemberAccess { name } => ExprKind::MemberAccess { value, name },
+                            PostfixType::MacroCallNoArgs => ExprKind::MacroCallNoArgs(value),
+                        };
+
+                        Expr::new(kind, span)
                     },
                 );
 
@@ -454,7 +250,6 @@ fn parser<'a>() -> parser_type!('a, String) {
             // TODO add space " " handling
 
             // TODO fold binary operators
-            // if a fold occurs then printbehavior -> implicit
             value
         })
         .labelled("expression");
@@ -467,157 +262,39 @@ fn parser<'a>() -> parser_type!('a, String) {
         .allow_trailing()
         .collect::<Vec<_>>()
         .labelled("statement")
-    });
-
-    choice((
-        just("SCGT").map(|_| {
-            let _ = open::that("https://github.com/kr8gz/scgt/");
-            ":)".to_string()
-        }),
-
-        global
-            .map_with_state(|stmts: Vec<SpwnCode>, _, state: &mut State| {
-                let mut code = format_stmts(&stmts, state, true, None);
-
-                if !state.variables.is_empty() {
-                    code = format!("{}\n{}\n{code}",
-                        "// Initialize variables used",
-                        state.variables
-                            .iter()
-                            .rfold(String::new(), |rest, var| {
-                                format!("let {var} = null\n{rest}")
-                            })
-                    );
-                }
-
-                if !state.helpers.is_empty() {
-                    code = format!("{}\n{}{code}",
-                        "// Automatically generated helper functions",
-                        state.helpers
-                            .iter()
-                            .rfold(String::new(), |rest, helper| {
-                                let code = helper.spwn_impl().replace("    ", &state.get_indent());
-                                format!("{} = {code}\n\n{rest}", helper.spwn_name())
-                            })
-                    );
-                }
-
-                format!("{code}\n")
-            })
-    ))
+    })
 }
 
-fn format_stmts(
-    stmts: &Vec<SpwnCode>,
-    state: &mut State,
-    global: bool,
-    return_fmt: Option<&str>,
-) -> String {
-    if stmts.is_empty() {
-        String::new()
-    } else {
-        let last_index = stmts.len() - 1;
-        let indent = if global { String::new() } else { state.get_indent() };
-
-        stmts
-            .iter()
-            .enumerate()
-            .map(|(i, code)| {
-                let comment = state.source[code.span.start..code.span.end]
-                    .lines()
-                    .map(|line| format!("{indent}// {line}\n"))
-                    .collect::<String>();
-
-                let code = match return_fmt {
-                    Some(r) if i == last_index => r.replace('#', &code.get_code(true, state)),
-                    _ => match code.print {
-                        PrintBehavior::Explicit => code.get_code(false, state).to_string(),
-                        PrintBehavior::Implicit => format!("$.print({})", code.get_code(true, state)),
-                    }
-                }
-                .lines()
-                .map(|line| format!("{indent}{line}"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            
-                format!("{comment}{code}")
-            })
-            .collect::<Vec<_>>()
-            .join(if global { "\n\n" } else { "\n" })
-    }
+/// Parses code in tests, which should be free of syntax errors.
+#[cfg(test)]
+pub fn parse_test_code(code: &str) -> Vec<Expr> {
+    let (ast, errors) = parse(code).into_output_errors();
+    assert!(errors.is_empty(), "{code:?} should parse");
+    ast.expect("test code should parse")
 }
 
-fn wrap_with_block(mut code: String, indent: bool, state: &mut State) -> String {
-    if indent {
-        code = code
-            .lines()
-            .map(|line| format!("{}{line}", state.get_indent()))
-            .collect::<Vec<_>>()
-            .join("\n");
-    }
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    format!("() {{\n{code}\n}} ()")
-}
+    #[test]
+    fn trailing_space() {
+        let ast = parse_test_code("2 3");
+        assert!(matches!(&ast[..], [Expr { kind: ExprKind::ShortMultiplication(n, value), .. }]
+            if n == "2" && matches!(&value.kind, ExprKind::Number(m) if m == "3")));
 
-fn format_loop(
-    start: &str,
-    stmts: &Vec<SpwnCode>,
-    span: SimpleSpan,
-    state: &mut State,
-) -> SpwnCode {
-    let expr;
-    let stmt;
-
-    if stmts.is_empty() {
-        let code = format!("{start} {{ }}");
-        stmt = CodeVariables::none(code.clone());
-        expr = CodeVariables::none(wrap_with_block(code, true, state))
-    } else {
-        stmt = CodeVariables::none(format!("{start} {{\n{}\n}}", format_stmts(stmts, state, false, None)));
-
-        let arr_name = format!("_scgt_loop_{}", state.depth);
-        let mut code = format_stmts(stmts, state, false, Some(&format!("{arr_name}.push(#)")));
-        code = format!("let {arr_name} = []\n{start} {{\n{code}\n}}\nreturn {arr_name}");
-        code = wrap_with_block(code, true, state);
-
-        let mut variables = BTreeSet::new();
-        variables.insert(arr_name);
-        expr = CodeVariables { code, helpers: None, variables: Some(variables) };
-    }
+        let ast = parse_test_code("ab c");
+        assert!(matches!(&ast[..], [a, c]
+            if matches!(&a.kind, ExprKind::Ident(name) if name == "ab") && matches!(&c.kind, ExprKind::Ident(name) if name == "c")));
 
-    SpwnCode {
-        expr, stmt: Some(stmt),
-        span,
-        print: PrintBehavior::Explicit,
+        let ast = parse_test_code("2 .5");
+        assert!(matches!(&ast[..], [Expr { kind: ExprKind::ShortMultiplication(n, value), .. }]
+            if n == "2" && matches!(&value.kind, ExprKind::Number(m) if m == "0.5")));
     }
-}
 
-fn format_assign(target: String, value: String, span: SimpleSpan) -> SpwnCode {
-    let mut helpers = BTreeSet::new();
-    let set = HelperFunction::Set;
-    helpers.insert(set);
-
-    let variables = text::ident::<_, _, extra::Default>()
-        .parse(target.as_str())
-        .output()
-        .map(|target| {
-            let mut variables = BTreeSet::new();
-            variables.insert(target.to_string());
-            variables
-        });
-
-    SpwnCode {
-        expr: CodeVariables {
-            code: format!("{}({target}, {value})", set.spwn_name()),
-            helpers: Some(helpers),
-            variables: variables.clone(),
-        },
-        stmt: Some(CodeVariables {
-            code: format!("{target} = {value}"),
-            helpers: None,
-            variables,
-        }),
-        span,
-        print: PrintBehavior::Explicit,
+    #[test]
+    fn single_space_only() {
+        assert!(parse("a  b").has_errors());
+        assert!(parse("2  3").has_errors());
     }
 }
@@ -0,0 +1,103 @@
+//! Entry point for the in-browser playground in `playground/`, exported to JavaScript when building for WebAssembly.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::style::{Comments, Indent};
+use crate::target::Target;
+use crate::Options;
+
+/// Name of the source file in diagnostics.
+const FILE_NAME: &str = "playground.scgt";
+
+/// Options that can be set in the playground, named like the command line arguments.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PlaygroundOptions {
+    target: Option<String>,
+    comments: Option<Comments>,
+    indent_size: Option<usize>,
+    tabs: Option<bool>,
+}
+
+impl PlaygroundOptions {
+    fn options(&self) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        if let Some(target) = &self.target {
+            options.target = Target::from_str(target, false).map_err(|_| format!("Unknown target {target:?}"))?;
+        }
+        if let Some(comments) = self.comments {
+            options.style.comments = comments;
+        }
+        if self.tabs == Some(true) {
+            options.style.indent = Indent::Tabs;
+        } else if let Some(size) = self.indent_size {
+            options.style.indent = Indent::Spaces(size);
+        }
+
+        Ok(options)
+    }
+}
+
+#[derive(Serialize)]
+struct PlaygroundOutput<D> {
+    /// Generated SPWN code, or `null` if there were invalid options or the code couldn't be parsed.
+    code: Option<String>,
+    diagnostics: Vec<D>,
+    /// Message about invalid options.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Compiles SCGT code with options given as a JSON object, returning the generated code and diagnostics as JSON.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = compile))]
+pub fn compile_json(source: &str, options: &str) -> String {
+    let options = match serde_json::from_str::<PlaygroundOptions>(options) {
+        Ok(options) => options.options(),
+        Err(err) => Err(format!("Invalid options: {err}")),
+    };
+
+    let options = match options {
+        Ok(options) => options,
+        Err(error) => {
+            let output = PlaygroundOutput::<()> { code: None, diagnostics: Vec::new(), error: Some(error) };
+            return serde_json::to_string(&output).expect("playground output should serialize");
+        }
+    };
+
+    let source = source.replace("\r\n", "\n");
+    let output = crate::compile(&source, &options);
+    let json = PlaygroundOutput {
+        code: output.code,
+        diagnostics: output.diagnostics.iter().map(|diagnostic| diagnostic.json(FILE_NAME, &source)).collect(),
+        error: None,
+    };
+
+    serde_json::to_string(&json).expect("playground output should serialize")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_output() {
+        assert_eq!(
+            compile_json("a!2;\r\n$a", r#"{"comments": "none", "target": "1.0"}"#),
+            r#"{"code":"mut a = 2\n\n$.print(a)\n","diagnostics":[]}"#,
+        );
+
+        let output: serde_json::Value = serde_json::from_str(&compile_json("a!", "{}")).unwrap();
+        assert_eq!(output["code"], serde_json::Value::Null);
+        assert_eq!(output["diagnostics"][0]["severity"], "error");
+        assert_eq!(output["diagnostics"][0]["file"], FILE_NAME);
+
+        assert_eq!(
+            compile_json("", r#"{"target": "2.0"}"#),
+            r#"{"code":null,"diagnostics":[],"error":"Unknown target \"2.0\""}"#,
+        );
+    }
+}
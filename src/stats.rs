@@ -0,0 +1,169 @@
+//! Size statistics for comparing golfing attempts, for `--stats`.
+
+use crate::parser;
+use crate::util::first_line;
+use crate::util::diagnostics::line_column;
+
+/// Size of a piece of SCGT code.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Size {
+    pub bytes: usize,
+    pub chars: usize,
+    /// Bytes when encoded in the SCGT code page, see [`code_page_len`].
+    pub code_page: usize,
+}
+
+impl Size {
+    pub fn of(code: &str) -> Self {
+        Self {
+            bytes: code.len(),
+            chars: code.chars().count(),
+            code_page: code.chars().map(code_page_len).sum(),
+        }
+    }
+}
+
+/// Characters of the SCGT code page for the bytes 0x80 to 0x9F, which are the ones of Windows-1252.
+/// The bytes that Windows-1252 leaves undefined stand for the control characters with the same code point.
+const CODE_PAGE_0X80: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ',
+];
+
+/// Bytes taken up by a character in the SCGT code page.
+///
+/// The code page has a single byte for every ASCII character, for the characters of [`CODE_PAGE_0X80`] and for
+/// U+00A0 to U+00FF, which fill the bytes 0xA0 to 0xFF. Characters outside of it count with their UTF-8 length.
+pub fn code_page_len(c: char) -> usize {
+    if c.is_ascii() || ('\u{A0}'..='\u{FF}').contains(&c) || CODE_PAGE_0X80.contains(&c) {
+        1
+    } else {
+        c.len_utf8()
+    }
+}
+
+pub struct StatementStats {
+    /// 1-based line of the start of the statement.
+    pub line: usize,
+    pub code: String,
+    pub size: Size,
+}
+
+pub struct Stats {
+    /// Size of the source after normalizing `\r\n` to `\n`, which is what gets compiled.
+    pub source: Size,
+    /// Bytes of the file as it was read.
+    pub file_bytes: usize,
+    /// Number of `\r\n` line endings that were normalized.
+    pub crlf: usize,
+    pub spwn_bytes: usize,
+    pub helpers: usize,
+    /// Top-level statements, empty if the source couldn't be parsed.
+    pub statements: Vec<StatementStats>,
+    /// Everything outside of the top-level statements, such as newlines between them.
+    pub between_statements: Size,
+}
+
+impl Stats {
+    /// Statistics for a file as it was read, before normalizing line endings, and the SPWN code generated from it.
+    pub fn new(file_source: &str, spwn: &str, helpers: usize) -> Self {
+        let source = file_source.replace("\r\n", "\n");
+
+        let statements = parser::parse(&source)
+            .into_output()
+            .unwrap_or_default()
+            .iter()
+            .map(|stmt| {
+                let code = &source[stmt.span.into_range()];
+                StatementStats {
+                    line: line_column(&source, stmt.span.start).0,
+                    code: code.to_string(),
+                    size: Size::of(code),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let total = Size::of(&source);
+        let between_statements = statements.iter().fold(total, |rest, stmt| Size {
+            bytes: rest.bytes - stmt.size.bytes,
+            chars: rest.chars - stmt.size.chars,
+            code_page: rest.code_page - stmt.size.code_page,
+        });
+
+        Self {
+            source: total,
+            file_bytes: file_source.len(),
+            crlf: file_source.matches("\r\n").count(),
+            spwn_bytes: spwn.len(),
+            helpers,
+            statements,
+            between_statements,
+        }
+    }
+
+    /// Readable report, given the paths of the source and generated files.
+    pub fn report(&self, file: &str, output: &str) -> String {
+        let Size { bytes, chars, code_page } = self.source;
+        let mut lines = vec![
+            format!("{file}: {bytes} bytes, {chars} chars, {code_page} bytes in the SCGT code page"),
+        ];
+        if self.crlf > 0 {
+            lines.push(format!(
+                "  {} \\r\\n line endings counted as \\n, the file itself has {} bytes",
+                self.crlf, self.file_bytes,
+            ));
+        }
+        lines.push(format!(
+            "{output}: {} bytes, {} helper{}",
+            self.spwn_bytes,
+            self.helpers,
+            if self.helpers == 1 { "" } else { "s" },
+        ));
+
+        if !self.statements.is_empty() {
+            lines.push(String::from("  line  bytes  chars  code page  statement"));
+
+            let row = |line: String, size: Size, code: &str| {
+                format!("  {line:>4}  {:>5}  {:>5}  {:>9}  {code}", size.bytes, size.chars, size.code_page)
+            };
+
+            for stmt in &self.statements {
+                lines.push(row(stmt.line.to_string(), stmt.size, &first_line(&stmt.code)));
+            }
+            lines.push(row(String::new(), self.between_statements, "(between statements)"));
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(Size::of("$'é\n$'€"), Size { bytes: 10, chars: 7, code_page: 7 });
+        assert_eq!(Size::of("'€'→"), Size { bytes: 8, chars: 4, code_page: 6 });
+
+        let stats = Stats::new("a!2;\r\n$\\é\r\nb`\r\n", "let a = 2\n", 0);
+        assert_eq!(stats.source, Size { bytes: 13, chars: 12, code_page: 12 });
+        assert_eq!((stats.file_bytes, stats.crlf, stats.spwn_bytes), (16, 3, 10));
+        assert_eq!(stats.statements.iter().map(|stmt| (stmt.line, stmt.size.bytes)).collect::<Vec<_>>(), [(1, 4), (2, 7)]);
+        assert_eq!(stats.between_statements, Size { bytes: 2, chars: 2, code_page: 2 });
+    }
+
+    #[test]
+    fn report() {
+        let stats = Stats::new("a!2;\r\n$\\x\r\ny`", "let a = 2\n", 1);
+        assert_eq!(stats.report("in.scgt", "out.spwn"), [
+            "in.scgt: 11 bytes, 11 chars, 11 bytes in the SCGT code page",
+            "  2 \\r\\n line endings counted as \\n, the file itself has 13 bytes",
+            "out.spwn: 10 bytes, 1 helper",
+            "  line  bytes  chars  code page  statement",
+            "     1      4      4          4  a!2;",
+            "     2      6      6          6  $\\x…",
+            "            1      1          1  (between statements)",
+        ].join("\n"));
+    }
+}
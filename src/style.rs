@@ -0,0 +1,146 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Formatting of generated code.
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub indent: Indent,
+    /// Number of empty lines between top-level statements.
+    pub blank_lines: usize,
+    pub brace_style: BraceStyle,
+    /// Whether generated files end with a newline.
+    pub trailing_newline: bool,
+    pub comments: Comments,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            indent: Indent::Spaces(4),
+            blank_lines: 1,
+            brace_style: BraceStyle::SameLine,
+            trailing_newline: true,
+            comments: Comments::EveryStatement,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BraceStyle {
+    /// Opening braces at the end of the line that starts the block.
+    SameLine,
+    /// Opening braces on their own line.
+    NextLine,
+}
+
+/// Comments that refer generated statements back to the SCGT source.
+#[derive(ValueEnum, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Comments {
+    /// No comments.
+    None,
+    /// The source of top-level statements.
+    TopLevelOnly,
+    /// The source of every statement.
+    EveryStatement,
+    /// The line and column where every statement starts.
+    Span,
+}
+
+/// Number of spaces that one level of indentation takes up in helper implementations.
+const HELPER_INDENT_SIZE: usize = 4;
+
+impl Style {
+    /// One level of indentation.
+    pub fn indent(&self) -> String {
+        match self.indent {
+            Indent::Spaces(n) => " ".repeat(n),
+            Indent::Tabs => String::from("\t"),
+        }
+    }
+
+    /// Separator between top-level statements.
+    pub fn separator(&self) -> String {
+        "\n".repeat(self.blank_lines + 1)
+    }
+
+    /// Block with an already indented body.
+    pub fn block(&self, head: &str, body: &str) -> String {
+        format!("{}{{\n{body}\n}}", self.before_brace(head))
+    }
+
+    /// Block without any statements, such as the body of an empty loop.
+    pub fn empty_block(&self, head: &str) -> String {
+        format!("{}{{ }}", self.before_brace(head))
+    }
+
+    /// Start of a block up to its opening brace.
+    fn before_brace(&self, head: &str) -> String {
+        match self.brace_style {
+            BraceStyle::SameLine => format!("{head} "),
+            BraceStyle::NextLine => format!("{head}\n"),
+        }
+    }
+
+    pub fn end_file(&self, code: &str) -> String {
+        if self.trailing_newline {
+            format!("{code}\n")
+        } else {
+            code.to_string()
+        }
+    }
+
+    /// Converts the indentation at the start of each line of a helper implementation to this style.
+    pub fn reindent(&self, code: &str) -> String {
+        let indent = self.indent();
+
+        code.lines()
+            .map(|line| {
+                let content = line.trim_start_matches([' ', '\t']);
+                let leading = &line[..line.len() - content.len()];
+
+                let (levels, spaces) = leading.chars().fold((0, 0), |(levels, spaces), c| match c {
+                    '\t' => (levels + 1, 0),
+                    _ if spaces + 1 == HELPER_INDENT_SIZE => (levels + 1, 0),
+                    _ => (levels, spaces + 1),
+                });
+
+                format!("{}{}{content}", indent.repeat(levels), " ".repeat(spaces))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reindent() {
+        let style = Style { indent: Indent::Tabs, ..Style::default() };
+        assert_eq!(
+            style.reindent("(v) {\n    $.print(\"a    b\")\n        \tx\n  y\n}"),
+            "(v) {\n\t$.print(\"a    b\")\n\t\t\tx\n  y\n}",
+        );
+
+        let style = Style { indent: Indent::Spaces(2), ..Style::default() };
+        assert_eq!(style.reindent("a\n    b\n        c"), "a\n  b\n    c");
+    }
+
+    #[test]
+    fn braces() {
+        let style = Style { brace_style: BraceStyle::NextLine, ..Style::default() };
+        assert_eq!(style.block("while true", "    a"), "while true\n{\n    a\n}");
+        assert_eq!(style.empty_block("while true"), "while true\n{ }");
+        assert_eq!(Style::default().block("()", "    a"), "() {\n    a\n}");
+        assert_eq!(Style::default().empty_block("while true"), "while true { }");
+    }
+}
@@ -0,0 +1,52 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// SPWN version that generated code is written for.
+#[derive(ValueEnum, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Target {
+    /// SPWN v0.8, the latest stable release.
+    #[value(name = "0.8")]
+    #[serde(rename = "0.8")]
+    V0_8,
+    /// SPWN v0.9 and the upcoming v1.0.
+    #[value(name = "0.9", alias = "1.0")]
+    #[serde(rename = "0.9", alias = "1.0")]
+    V0_9,
+}
+
+impl Target {
+    /// Whether reading a variable can share its value with the variable, see `get.spwn`.
+    pub fn has_clone_bug(&self) -> bool {
+        *self == Self::V0_8
+    }
+
+    /// Keyword that declares a variable which can be reassigned.
+    pub fn declaration_keyword(&self) -> &'static str {
+        match self {
+            Self::V0_8 => "let",
+            Self::V0_9 => "mut",
+        }
+    }
+
+    /// Declaration of variables that are never reassigned, such as the names destructured from an import.
+    pub fn constant_declaration(&self, pattern: &str, value: &str) -> String {
+        match self {
+            Self::V0_8 => format!("let {pattern} = {value}"),
+            Self::V0_9 => format!("{pattern} = {value}"),
+        }
+    }
+
+    /// Call of a macro without arguments right where it is defined, which is how blocks are turned into expressions.
+    pub fn call_in_place(&self, macro_code: &str) -> String {
+        match self {
+            Self::V0_8 => format!("{macro_code} ()"),
+            Self::V0_9 => format!("({macro_code})()"),
+        }
+    }
+
+    /// Trigger function with an already indented body. `!{` is a single token, so the brace
+    /// never moves to the next line.
+    pub fn trigger_function(&self, body: &str) -> String {
+        format!("!{{\n{body}\n}}")
+    }
+}
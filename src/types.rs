@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ast::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Type {
+    Number,
+    Bool,
+    String,
+    Array,
+    Group,
+    Color,
+    Item,
+    Block,
+    Null,
+    Macro,
+    TriggerFunction,
+    Indicator,
+}
+
+impl Type {
+    /// SPWN type indicator of this type.
+    pub fn spwn_name(&self) -> &'static str {
+        match self {
+            Self::Number => "@number",
+            Self::Bool => "@bool",
+            Self::String => "@string",
+            Self::Array => "@array",
+            Self::Group => "@group",
+            Self::Color => "@color",
+            Self::Item => "@item",
+            Self::Block => "@block",
+            Self::Null => "@NULL",
+            Self::Macro => "@macro",
+            Self::TriggerFunction => "@trigger_function",
+            Self::Indicator => "@type_indicator",
+        }
+    }
+
+    /// Whether reading a variable of this type can share its value with the variable
+    /// on targets with the clone bug (see `get.spwn`).
+    pub fn shared_on_read(&self) -> bool {
+        matches!(self, Self::Array | Self::Macro | Self::TriggerFunction)
+    }
+}
+
+impl Constant {
+    pub fn value_type(&self) -> Type {
+        match self {
+            Self::EmptyArray => Type::Array,
+            Self::Block => Type::Block,
+            Self::Color => Type::Color,
+            Self::Item => Type::Item,
+            Self::False | Self::True => Type::Bool,
+            Self::Group => Type::Group,
+            Self::Null => Type::Null,
+            Self::EmptyString => Type::String,
+        }
+    }
+}
+
+/// Types known at compile time.
+#[derive(Default)]
+pub struct Types {
+    variables: BTreeMap<String, Type>,
+}
+
+impl Types {
+    /// Type of the value of an expression, if it is always the same.
+    pub fn of(&self, expr: &Expr) -> Option<Type> {
+        match &expr.kind {
+            ExprKind::Number(_) => Some(Type::Number),
+            ExprKind::String(_) => Some(Type::String),
+            ExprKind::TypeIndicator(_) => Some(Type::Indicator),
+            ExprKind::Constant(c) => Some(c.value_type()),
+            ExprKind::LoopVariable(_) => None,
+            ExprKind::Ident(name) => self.variables.get(name).copied(),
+
+            ExprKind::ShortMultiplication(_, expr) => match self.of(expr)? {
+                Type::Bool => Some(Type::Number),
+                t => Some(t),
+            },
+            ExprKind::Invert(expr) | ExprKind::Print(expr) => self.of(expr),
+            ExprKind::OnTouch(_) => None,
+
+            ExprKind::Block(stmts) => match stmts.last() {
+                Some(stmt) => self.of(stmt),
+                None => Some(Type::Null),
+            },
+            ExprKind::TriggerFunction(_) => Some(Type::TriggerFunction),
+            ExprKind::Macro { .. } => Some(Type::Macro),
+            // an empty infinite loop never produces a value
+            ExprKind::Loop(stmts) => (!stmts.is_empty()).then_some(Type::Array),
+
+            ExprKind::Assign { value, .. } => self.of(value),
+            ExprKind::MemberAccess { .. } | ExprKind::MacroCallNoArgs(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Facts<'a> {
+    assignments: Vec<&'a Expr>,
+    /// Whether the variable might be read before it has been assigned.
+    maybe_null: bool,
+    /// Whether the variable is assigned somewhere its order relative to reads is unknown.
+    unordered: bool,
+}
+
+/// Infers the types of variables that are only ever assigned values of one known type.
+pub fn infer(stmts: &[Expr]) -> Types {
+    let mut collector = Collector::default();
+    stmts.iter().for_each(|stmt| collector.visit(stmt));
+
+    let candidates = collector.variables
+        .into_iter()
+        .filter(|(name, facts)| {
+            !facts.maybe_null && !facts.unordered && !collector.macro_params.contains(name)
+        })
+        .collect::<Vec<_>>();
+
+    let mut types = Types::default();
+
+    // every iteration can only add new types, since the types of values only depend on known variable types
+    loop {
+        let mut changed = false;
+
+        for (name, facts) in &candidates {
+            if types.variables.contains_key(*name) {
+                continue;
+            }
+
+            let mut assigned = facts.assignments.iter().map(|value| types.of(value));
+
+            if let Some(Some(t)) = assigned.next() {
+                if assigned.all(|other| other == Some(t)) {
+                    types.variables.insert(name.to_string(), t);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break types;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Collector<'a> {
+    variables: BTreeMap<&'a str, Facts<'a>>,
+    macro_params: BTreeSet<&'a str>,
+    params_in_scope: Vec<&'a [String]>,
+    function_depth: usize,
+}
+
+impl<'a> Collector<'a> {
+    fn variable(&mut self, name: &'a str) -> Option<&mut Facts<'a>> {
+        if self.params_in_scope.iter().any(|params| params.iter().any(|param| param == name)) {
+            None
+        } else {
+            Some(self.variables.entry(name).or_default())
+        }
+    }
+
+    fn visit(&mut self, expr: &'a Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                if let Some(facts) = self.variable(name) {
+                    if facts.assignments.is_empty() {
+                        facts.maybe_null = true;
+                    }
+                }
+            }
+
+            ExprKind::Assign { target, value } => {
+                self.visit(value);
+
+                match &target.kind {
+                    ExprKind::Ident(name) => {
+                        let in_function = self.function_depth > 0;
+                        if let Some(facts) = self.variable(name) {
+                            facts.assignments.push(value);
+                            facts.unordered |= in_function;
+                        }
+                    }
+                    _ => self.visit(target),
+                }
+            }
+
+            ExprKind::Macro { params, body } => {
+                self.macro_params.extend(params.iter().map(String::as_str));
+                self.params_in_scope.push(params);
+                self.function_depth += 1;
+                body.iter().for_each(|stmt| self.visit(stmt));
+                self.function_depth -= 1;
+                self.params_in_scope.pop();
+            }
+
+            ExprKind::TriggerFunction(body) => {
+                self.function_depth += 1;
+                body.iter().for_each(|stmt| self.visit(stmt));
+                self.function_depth -= 1;
+            }
+
+            _ => expr.for_each_child(|child| self.visit(child)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn last_type(code: &str) -> Option<Type> {
+        let ast = crate::parser::parse_test_code(code);
+        infer(&ast).of(ast.last().expect("test code should not be empty"))
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(last_type("3"), Some(Type::Number));
+        assert_eq!(last_type("'a"), Some(Type::String));
+        assert_eq!(last_type("G"), Some(Type::Group));
+        assert_eq!(last_type("!T"), Some(Type::Bool));
+        assert_eq!(last_type("2T"), Some(Type::Number));
+        assert_eq!(last_type("(1;"), Some(Type::Number));
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(last_type("a!3;\nb!a;\nb"), Some(Type::Number));
+        assert_eq!(last_type("a!3;\na!'c;\na"), None);
+        assert_eq!(last_type("$a\na!3;\na"), None);
+        assert_eq!(last_type("fMa!3;;\na"), None);
+        assert_eq!(last_type("x!3;\nfX$x;\nx"), None);
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use chumsky::error::{Rich, RichPattern, RichReason};
+use serde::Serialize;
+
+#[derive(Serialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning about a span of SCGT source code.
+///
+/// All spans are byte offsets into the source.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Range<usize>,
+    pub labels: Vec<(Range<usize>, String)>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn from_rich(err: &Rich<char>) -> Self {
+        let span = err.span().into_range();
+
+        let (message, label) = match err.reason() {
+            RichReason::ExpectedFound { expected, found } => (
+                match found {
+                    Some(c) => format!("Unexpected {:?}", **c),
+                    None => "Unexpected end of input".to_string(),
+                },
+                format_expected(expected),
+            ),
+            reason => (reason.to_string(), reason.to_string()),
+        };
+
+        let mut labels = vec![(span.clone(), label)];
+        labels.extend(
+            err.contexts()
+                .map(|(label, span)| (span.into_range(), format!("while parsing this {label}")))
+        );
+
+        Self {
+            severity: Severity::Error,
+            message,
+            span,
+            labels,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn eprint(&self, file: &str, source: &str) {
+        let (kind, color) = match self.severity {
+            Severity::Error => (ReportKind::Error, Color::Red),
+            Severity::Warning => (ReportKind::Warning, Color::Yellow),
+        };
+
+        // ariadne expects char offsets rather than byte offsets
+        let char_span = |span: &Range<usize>| {
+            char_offset(source, span.start)..char_offset(source, span.end)
+        };
+
+        let mut report = Report::build(kind, file, char_offset(source, self.span.start))
+            .with_message(&self.message)
+            .with_labels(
+                self.labels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (span, message))| {
+                        Label::new((file, char_span(span)))
+                            .with_message(message)
+                            .with_color(if i == 0 { color } else { Color::Blue })
+                    })
+            );
+
+        for note in &self.notes {
+            report = report.with_note(note);
+        }
+
+        let _ = report.finish().eprint((file, Source::from(source)));
+    }
+
+    /// Formats the diagnostic as a single line of JSON.
+    pub fn to_json(&self, file: &str, source: &str) -> String {
+        serde_json::to_string(&self.json(file, source)).expect("diagnostics should always be serializable")
+    }
+
+    /// The diagnostic in the format of [`Diagnostic::to_json`], for embedding in other JSON.
+    pub fn json<'a>(&'a self, file: &'a str, source: &str) -> impl Serialize + 'a {
+        JsonDiagnostic {
+            file,
+            severity: self.severity,
+            message: &self.message,
+            span: JsonSpan::new(&self.span, source),
+            labels: self.labels
+                .iter()
+                .map(|(span, message)| JsonLabel {
+                    span: JsonSpan::new(span, source),
+                    message,
+                })
+                .collect(),
+            notes: &self.notes,
+        }
+    }
+}
+
+fn format_expected(expected: &[RichPattern<char>]) -> String {
+    let mut expected = expected
+        .iter()
+        .map(|pattern| match pattern {
+            RichPattern::Token(c) => format!("{:?}", **c),
+            RichPattern::Label(label) => label.to_string(),
+            RichPattern::EndOfInput => "end of input".to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    expected.sort();
+    expected.dedup();
+
+    match expected.split_last() {
+        None => "Unexpected input".to_string(),
+        Some((last, [])) => format!("Expected {last}"),
+        Some((last, rest)) => format!("Expected {} or {last}", rest.join(", ")),
+    }
+}
+
+fn char_offset(source: &str, byte_offset: usize) -> usize {
+    source[..byte_offset].chars().count()
+}
+
+/// 1-based line and column (in chars) of a byte offset.
+pub fn line_column(source: &str, byte_offset: usize) -> (usize, usize) {
+    let before = &source[..byte_offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    file: &'a str,
+    severity: Severity,
+    message: &'a str,
+    span: JsonSpan,
+    labels: Vec<JsonLabel<'a>>,
+    notes: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    span: JsonSpan,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    start: usize,
+    end: usize,
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+impl JsonSpan {
+    fn new(span: &Range<usize>, source: &str) -> Self {
+        let (start_line, start_column) = line_column(source, span.start);
+        let (end_line, end_column) = line_column(source, span.end);
+
+        Self {
+            start: span.start,
+            end: span.end,
+            start_line, start_column,
+            end_line, end_column,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line_columns() {
+        let source = "ab\ncdé\nf";
+
+        assert_eq!(line_column(source, 0), (1, 1));
+        assert_eq!(line_column(source, 2), (1, 3));
+        assert_eq!(line_column(source, 3), (2, 1));
+        assert_eq!(line_column(source, 7), (2, 4));
+        assert_eq!(line_column(source, 8), (3, 1));
+    }
+
+    #[test]
+    fn json_output() {
+        let source = "ab\nc";
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: String::from("message"),
+            span: 3..4,
+            labels: vec![(0..2, String::from("label"))],
+            notes: vec![String::from("note")],
+        };
+
+        assert_eq!(
+            diagnostic.to_json("test.scgt", source),
+            concat!(
+                r#"{"file":"test.scgt","severity":"error","message":"message","#,
+                r#""span":{"start":3,"end":4,"start_line":2,"start_column":1,"end_line":2,"end_column":2},"#,
+                r#""labels":[{"span":{"start":0,"end":2,"start_line":1,"start_column":1,"end_line":1,"end_column":3},"message":"label"}],"#,
+                r#""notes":["note"]}"#,
+            ),
+        );
+    }
+}
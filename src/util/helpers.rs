@@ -1,23 +1,48 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, io, path::Path};
+
+use crate::target::Target;
+
 macro_rules! helper_functions {
     (
         % PREFIX $prefix:literal
-        $( $name:ident: $spwn_name:literal, )*
+        $( $name:ident: $spwn_name:literal $( [ $( $dep:ident ),* ] )?, )*
     ) => {
-        #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+        /// Prefix of generated identifiers unless another one is chosen.
+        ///
+        /// Helper implementations use it to refer to other helpers.
+        pub const DEFAULT_PREFIX: &str = $prefix;
+
+        #[allow(dead_code)] // not all helpers have syntax using them yet
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
         pub enum HelperFunction {
             $( $name ),*
         }
         
         impl HelperFunction {
-            pub fn spwn_name(&self) -> &'static str {
+            pub const ALL: &'static [HelperFunction] = &[ $( Self::$name ),* ];
+
+            /// Name of the helper without a prefix.
+            pub fn name(&self) -> &'static str {
                 match self {
-                    $( Self::$name => concat!($prefix, $spwn_name) ),*
+                    $( Self::$name => $spwn_name ),*
                 }
             }
         
-            pub fn spwn_impl(&self) -> &'static str {
+            pub fn spwn_impl(&self, target: Target) -> &'static str {
+                match target {
+                    Target::V0_8 => match self {
+                        $( Self::$name => include_str!(concat!("../spwn/0.8/", $spwn_name, ".spwn")) ),*
+                    },
+                    Target::V0_9 => match self {
+                        $( Self::$name => include_str!(concat!("../spwn/0.9/", $spwn_name, ".spwn")) ),*
+                    },
+                }
+            }
+
+            /// Other helpers called by the implementation of this helper.
+            pub fn dependencies(&self) -> &'static [HelperFunction] {
                 match self {
-                    $( Self::$name => include_str!(concat!("../spwn/", $spwn_name, ".spwn")) ),*
+                    $( Self::$name => &[ $( $( Self::$dep ),* )? ] ),*
                 }
             }
         }
@@ -31,8 +56,110 @@ helper_functions! {
     Get: "get",
     Invert: "invert",
     Iter: "iter",
-    Mod: "mod",
+    Mod: "mod" [Iter],
     Mul: "mul",
     Print: "print",
     Set: "set",
 }
+
+/// Implementations of helpers, which can be overridden by files in a directory.
+pub struct HelperImpls {
+    target: Target,
+    overrides: BTreeMap<HelperFunction, String>,
+}
+
+impl HelperImpls {
+    pub fn builtin(target: Target) -> Self {
+        Self { target, overrides: BTreeMap::new() }
+    }
+
+    /// Uses `{name}.spwn` files in `dir` where they exist, and built-in implementations otherwise.
+    pub fn load(target: Target, dir: &Path) -> io::Result<Self> {
+        let mut overrides = BTreeMap::new();
+
+        for &helper in HelperFunction::ALL {
+            match fs::read_to_string(dir.join(format!("{}.spwn", helper.name()))) {
+                Ok(code) => { overrides.insert(helper, code.replace("\r\n", "\n").trim_end().to_string()); }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Self { target, overrides })
+    }
+
+    pub fn get(&self, helper: HelperFunction) -> &str {
+        self.overrides.get(&helper).map_or_else(|| helper.spwn_impl(self.target), String::as_str)
+    }
+}
+
+/// Writes the built-in implementation of every helper to `dir`, returning the paths of the files written.
+///
+/// Existing files are left untouched so that edited helpers aren't lost.
+pub fn emit_builtin(target: Target, dir: &Path) -> io::Result<Vec<String>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+
+    for &helper in HelperFunction::ALL {
+        let path = dir.join(format!("{}.spwn", helper.name()));
+        if !path.exists() {
+            fs::write(&path, format!("{}\n", helper.spwn_impl(target)))?;
+            written.push(path.display().to_string());
+        }
+    }
+
+    Ok(written)
+}
+
+/// Adds all dependencies of the given helpers and orders them so that every helper comes after its dependencies.
+pub fn resolve_dependencies(helpers: impl IntoIterator<Item = HelperFunction>) -> Vec<HelperFunction> {
+    fn visit(helper: HelperFunction, visited: &mut BTreeSet<HelperFunction>, order: &mut Vec<HelperFunction>) {
+        if visited.insert(helper) {
+            for &dep in helper.dependencies() {
+                visit(dep, visited, order);
+            }
+            order.push(helper);
+        }
+    }
+
+    let mut helpers = helpers.into_iter().collect::<Vec<_>>();
+    helpers.sort();
+
+    let mut visited = BTreeSet::new();
+    let mut order = Vec::new();
+
+    for helper in helpers {
+        visit(helper, &mut visited, &mut order);
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use HelperFunction::*;
+
+    #[test]
+    fn overrides() {
+        let dir = std::env::temp_dir().join(format!("scgt_helpers_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("print.spwn"), "(v) => $.print(v)\r\n").unwrap();
+
+        let impls = HelperImpls::load(Target::V0_8, &dir).unwrap();
+        assert_eq!(impls.get(Print), "(v) => $.print(v)");
+        assert_eq!(impls.get(Get), Get.spwn_impl(Target::V0_8));
+
+        let written = emit_builtin(Target::V0_8, &dir).unwrap();
+        assert_eq!(written.len(), HelperFunction::ALL.len() - 1);
+        assert_eq!(fs::read_to_string(dir.join("print.spwn")).unwrap(), "(v) => $.print(v)\r\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dependencies_come_first() {
+        assert_eq!(resolve_dependencies([Print, Mod, Bool]), [Bool, Iter, Mod, Print]);
+        assert_eq!(resolve_dependencies([Iter, Mod]), [Iter, Mod]);
+    }
+}
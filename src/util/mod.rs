@@ -1,2 +1,22 @@
+pub mod diagnostics;
 pub mod errors;
 pub mod helpers;
+pub mod names;
+pub mod source_map;
+
+use clap::ValueEnum;
+
+/// Name of a value as it is written on the command line.
+pub fn value_name(value: &impl ValueEnum) -> String {
+    value.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Code cut off after its first line, so that multiline strings and blocks are shown on one line.
+pub fn first_line(code: &str) -> String {
+    match code.split_once('\n') {
+        Some((first, _)) => format!("{first}…"),
+        None => code.to_string(),
+    }
+}
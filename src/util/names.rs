@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::util::helpers::HelperFunction;
+
+/// Whether `name` has the form of a name handed out by code generation with the given prefix,
+/// meaning that generated names would be renamed to avoid it.
+pub fn is_generated(name: &str, prefix: &str) -> bool {
+    let Some(rest) = name.strip_prefix(prefix) else { return false };
+
+    // names that were taken get a counter appended
+    let base = match rest.rsplit_once('_') {
+        Some((base, n)) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => base,
+        _ => rest,
+    };
+
+    HelperFunction::ALL
+        .iter()
+        .map(HelperFunction::name)
+        .chain(["loop", "loop_i", "loop_j", "loop_k"])
+        .any(|generated| generated == base || generated == rest)
+}
+
+/// Hands out generated identifiers that never clash with user identifiers or with each other.
+pub struct Names {
+    prefix: String,
+    used: BTreeSet<String>,
+    stable: BTreeMap<String, String>,
+    counters: BTreeMap<String, usize>,
+}
+
+impl Names {
+    pub fn new(prefix: &str, user_names: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            used: user_names.into_iter().collect(),
+            stable: BTreeMap::new(),
+            counters: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the same name every time it is called with the same `base`.
+    pub fn stable(&mut self, base: &str) -> String {
+        if let Some(name) = self.stable.get(base) {
+            return name.clone();
+        }
+
+        let mut name = format!("{}{base}", self.prefix);
+        let mut n = 0;
+        while self.used.contains(&name) {
+            n += 1;
+            name = format!("{}{base}_{n}", self.prefix);
+        }
+
+        self.used.insert(name.clone());
+        self.stable.insert(base.to_string(), name.clone());
+        name
+    }
+
+    /// Returns a name that has never been returned before.
+    pub fn fresh(&mut self, base: &str) -> String {
+        let counter = self.counters.entry(base.to_string()).or_default();
+
+        loop {
+            *counter += 1;
+            let name = format!("{}{base}_{counter}", self.prefix);
+            if self.used.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn avoids_user_names() {
+        let mut names = Names::new("_scgt_", ["_scgt_get".to_string(), "_scgt_loop_2".to_string()]);
+
+        assert_eq!(names.stable("get"), "_scgt_get_1");
+        assert_eq!(names.stable("get"), "_scgt_get_1");
+        assert_eq!(names.stable("print"), "_scgt_print");
+
+        assert_eq!(names.fresh("loop"), "_scgt_loop_1");
+        assert_eq!(names.fresh("loop"), "_scgt_loop_3");
+    }
+
+    #[test]
+    fn stable_and_fresh_names_are_distinct() {
+        let mut names = Names::new("_scgt_", []);
+
+        assert_eq!(names.fresh("get"), "_scgt_get_1");
+        assert_eq!(names.stable("get_1"), "_scgt_get_1_1");
+    }
+
+    #[test]
+    fn generated_names() {
+        assert!(is_generated("_scgt_get", "_scgt_"));
+        assert!(is_generated("_scgt_loop_i_2", "_scgt_"));
+        assert!(is_generated("genloop_1", "gen"));
+        assert!(!is_generated("general", "gen"));
+        assert!(!is_generated("_scgt_foo", "_scgt_"));
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let mut names = Names::new("gen", ["genloop_1".to_string()]);
+
+        assert_eq!(names.stable("print"), "genprint");
+        assert_eq!(names.fresh("loop"), "genloop_2");
+    }
+}
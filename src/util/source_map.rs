@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use super::diagnostics::line_column;
+
+// generated code is marked with noncharacters, which should never appear in source code
+const START: char = '\u{FDD0}';
+const START_END: char = '\u{FDD1}';
+const END: char = '\u{FDD2}';
+
+/// Marks generated code as coming from a span of the source, to be picked up by [`strip_markers`].
+pub fn mark(code: &str, source: Range<usize>) -> String {
+    format!("{START}{}:{}{START_END}{code}{END}", source.start, source.end)
+}
+
+/// Whether a character is used by markers, which means it has to be escaped wherever it is copied into generated code.
+pub fn is_marker(c: char) -> bool {
+    matches!(c, START | START_END | END)
+}
+
+/// Replaces marker characters in text copied into generated code, such as source comments, with `\u{...}` escapes.
+pub fn escape_markers(text: &str) -> String {
+    text.chars()
+        .map(|c| if is_marker(c) { format!("\\u{{{:X}}}", u32::from(c)) } else { c.to_string() })
+        .collect()
+}
+
+/// A range of generated code and the span of source code it was generated from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mapping {
+    /// Byte offsets into the generated code.
+    pub generated: Range<usize>,
+    /// Byte offsets into the source code.
+    pub source: Range<usize>,
+}
+
+/// Removes all markers from generated code, returning the code without them and the ranges they marked.
+///
+/// Fails if the markers are malformed, which happens when marker characters were copied into the code without
+/// being escaped.
+pub fn strip_markers(marked: &str) -> Result<(String, Vec<Mapping>), String> {
+    let mut code = String::with_capacity(marked.len());
+    let mut open = Vec::new();
+    let mut mappings = Vec::new();
+    let mut chars = marked.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            START => {
+                let header = chars.by_ref().take_while(|&c| c != START_END).collect::<String>();
+                let invalid = || format!("Invalid source map marker `{header}` in generated code");
+                let (start, end) = header.split_once(':').ok_or_else(invalid)?;
+                let source = start.parse().map_err(|_| invalid())?..end.parse().map_err(|_| invalid())?;
+                open.push((code.len(), source));
+            }
+            END => {
+                let (start, source) = open.pop().ok_or("Unbalanced source map markers in generated code")?;
+                mappings.push(Mapping { generated: start..code.len(), source });
+            }
+            c => code.push(c),
+        }
+    }
+
+    if !open.is_empty() {
+        return Err(String::from("Unbalanced source map markers in generated code"));
+    }
+
+    mappings.sort_by_key(|mapping| (mapping.generated.start, std::cmp::Reverse(mapping.generated.end)));
+    Ok((code, mappings))
+}
+
+/// Mappings between a generated SPWN file and its SCGT source.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SourceMap {
+    pub version: u32,
+    /// Path of the generated file.
+    pub file: String,
+    /// Path of the source file.
+    pub source: String,
+    pub mappings: Vec<JsonMapping>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JsonMapping {
+    pub generated: GeneratedRange,
+    /// Byte offsets into the source.
+    pub source: Range<usize>,
+}
+
+/// 1-based lines and columns (in chars), the end being exclusive.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GeneratedRange {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl SourceMap {
+    pub fn new(file: &str, source: &str, code: &str, mappings: &[Mapping]) -> Self {
+        let mappings = mappings
+            .iter()
+            .map(|mapping| {
+                let (start_line, start_column) = line_column(code, mapping.generated.start);
+                let (end_line, end_column) = line_column(code, mapping.generated.end);
+                JsonMapping {
+                    generated: GeneratedRange { start_line, start_column, end_line, end_column },
+                    source: mapping.source.clone(),
+                }
+            })
+            .collect();
+
+        Self { version: 1, file: file.to_string(), source: source.to_string(), mappings }
+    }
+
+    /// Source span of the innermost mapping containing a 1-based line and column of the generated code.
+    pub fn source_span(&self, line: usize, column: usize) -> Option<Range<usize>> {
+        let position = (line, column);
+
+        self.mappings
+            .iter()
+            .filter(|mapping| {
+                let range = &mapping.generated;
+                (range.start_line, range.start_column) <= position && position < (range.end_line, range.end_column)
+            })
+            .min_by_key(|mapping| mapping.source.len())
+            .map(|mapping| mapping.source.clone())
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| err.to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("source maps should serialize")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn markers() {
+        let marked = format!("a\n{}", mark(&format!("b({})", mark("c", 4..5)), 2..6));
+        let (code, mappings) = strip_markers(&marked).unwrap();
+
+        assert_eq!(code, "a\nb(c)");
+        assert_eq!(mappings, [
+            Mapping { generated: 2..6, source: 2..6 },
+            Mapping { generated: 4..5, source: 4..5 },
+        ]);
+
+        let map = SourceMap::new("out.spwn", "in.scgt", &code, &mappings);
+        assert_eq!(map.to_json(), concat!(
+            r#"{"version":1,"file":"out.spwn","source":"in.scgt","mappings":["#,
+            r#"{"generated":{"start_line":2,"start_column":1,"end_line":2,"end_column":5},"source":{"start":2,"end":6}},"#,
+            r#"{"generated":{"start_line":2,"start_column":3,"end_line":2,"end_column":4},"source":{"start":4,"end":5}}]}"#,
+        ));
+
+        let map = SourceMap::from_json(&map.to_json()).unwrap();
+        assert_eq!(map.source_span(2, 3), Some(4..5));
+        assert_eq!(map.source_span(2, 1), Some(2..6));
+        assert_eq!(map.source_span(1, 1), None);
+    }
+
+    #[test]
+    fn malformed_markers() {
+        assert!(strip_markers(&format!("a{END}")).is_err());
+        assert!(strip_markers(&format!("{START}1{START_END}a{END}")).is_err());
+        assert!(strip_markers(&mark("a", 0..1).replace(END, "")).is_err());
+        assert_eq!(escape_markers(&format!("a{END}b")), "a\\u{FDD2}b");
+    }
+}
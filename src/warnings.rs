@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chumsky::span::SimpleSpan;
+use clap::ValueEnum;
+
+use crate::ast::*;
+use crate::util::diagnostics::{Diagnostic, Severity};
+use crate::util::{self, names};
+
+#[derive(ValueEnum, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Lint {
+    /// Every lint below.
+    All,
+    /// Identifiers that could collide with generated names.
+    ReservedName,
+    /// Variables that are read but never assigned.
+    UndefinedVariable,
+    /// Variables that are assigned but never read.
+    UnusedVariable,
+    /// Macro arguments that shadow a variable or an argument of an enclosing macro.
+    Shadowing,
+    /// `I`, `J` or `K` used outside of a loop.
+    LoopVariableOutsideLoop,
+}
+
+/// Which lints are reported. Every lint is enabled by default.
+#[derive(Default, Clone, Debug)]
+pub struct LintLevels {
+    allowed: BTreeSet<Lint>,
+}
+
+impl LintLevels {
+    pub fn warn(&mut self, lint: Lint) {
+        match lint {
+            Lint::All => self.allowed.clear(),
+            lint => { self.allowed.remove(&lint); }
+        }
+    }
+
+    pub fn allow(&mut self, lint: Lint) {
+        match lint {
+            Lint::All => self.allowed.extend(Lint::value_variants()),
+            lint => { self.allowed.insert(lint); }
+        }
+    }
+
+    fn is_enabled(&self, lint: Lint) -> bool {
+        !self.allowed.contains(&lint)
+    }
+}
+
+/// Checks for possible mistakes, assuming generated names start with `prefix`.
+pub fn check(stmts: &[Expr], levels: &LintLevels, prefix: &str) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(levels, prefix);
+    checker.check_stmts(stmts);
+    checker.finish()
+}
+
+#[derive(Default)]
+struct Usage {
+    assigned: Vec<SimpleSpan>,
+    read: Vec<SimpleSpan>,
+}
+
+struct Checker<'a> {
+    levels: &'a LintLevels,
+    prefix: &'a str,
+    warnings: Vec<Diagnostic>,
+
+    variables: BTreeMap<&'a str, Usage>,
+    /// Arguments of the macros being checked, with the spans of the macros.
+    macro_args: Vec<(&'a [String], SimpleSpan)>,
+    shadowing: Vec<(&'a str, SimpleSpan)>,
+    loop_depth: usize,
+}
+
+impl<'a> Checker<'a> {
+    fn new(levels: &'a LintLevels, prefix: &'a str) -> Self {
+        Self {
+            levels,
+            prefix,
+            warnings: Vec::new(),
+
+            variables: BTreeMap::new(),
+            macro_args: Vec::new(),
+            shadowing: Vec::new(),
+            loop_depth: 0,
+        }
+    }
+
+    fn warn(
+        &mut self,
+        lint: Lint,
+        message: String,
+        span: SimpleSpan,
+        labels: impl IntoIterator<Item = (SimpleSpan, String)>,
+    ) {
+        if self.levels.is_enabled(lint) {
+            self.warnings.push(Diagnostic {
+                severity: Severity::Warning,
+                message,
+                span: span.into_range(),
+                labels: labels
+                    .into_iter()
+                    .map(|(span, label)| (span.into_range(), label))
+                    .collect(),
+                notes: vec![format!("silence with `-A {}`", util::value_name(&lint))],
+            });
+        }
+    }
+
+    fn check_stmts(&mut self, stmts: &'a [Expr]) {
+        stmts.iter().for_each(|stmt| self.check_expr(stmt));
+    }
+
+    fn check_expr(&mut self, expr: &'a Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                if let Some(usage) = self.use_variable(name, expr.span) {
+                    usage.read.push(expr.span);
+                }
+            }
+
+            ExprKind::Assign { target, value } => {
+                match &target.kind {
+                    ExprKind::Ident(name) => {
+                        if let Some(usage) = self.use_variable(name, target.span) {
+                            usage.assigned.push(target.span);
+                        }
+                    }
+                    _ => self.check_expr(target),
+                }
+                self.check_expr(value);
+            }
+
+            ExprKind::LoopVariable(name) => {
+                if self.loop_depth == 0 {
+                    self.warn(
+                        Lint::LoopVariableOutsideLoop,
+                        format!("Loop variable `{name}` used outside of a loop"),
+                        expr.span,
+                        [(expr.span, "not inside any loop".to_string())],
+                    );
+                }
+            }
+
+            ExprKind::Loop(stmts) => {
+                self.loop_depth += 1;
+                self.check_stmts(stmts);
+                self.loop_depth -= 1;
+            }
+
+            ExprKind::Macro { params, body } => {
+                for param in params {
+                    let enclosing = self.macro_args
+                        .iter()
+                        .rev()
+                        .find(|(args, _)| args.contains(param))
+                        .map(|&(_, span)| span);
+
+                    match enclosing {
+                        Some(enclosing) => self.warn(
+                            Lint::Shadowing,
+                            format!("Macro argument `{param}` shadows the argument `{param}` of an enclosing macro"),
+                            expr.span,
+                            [
+                                (expr.span, format!("`{param}` is an argument of this macro")),
+                                (enclosing, "and of this one".to_string()),
+                            ],
+                        ),
+                        None => self.shadowing.push((param, expr.span)),
+                    }
+                }
+                self.macro_args.push((params, expr.span));
+                self.check_stmts(body);
+                self.macro_args.pop();
+            }
+
+            _ => expr.for_each_child(|child| self.check_expr(child)),
+        }
+    }
+
+    /// Returns `None` if the name refers to a macro argument.
+    fn use_variable(&mut self, name: &'a str, span: SimpleSpan) -> Option<&mut Usage> {
+        if names::is_generated(name, self.prefix) {
+            self.warn(
+                Lint::ReservedName,
+                format!("Identifier `{name}` has the form of a generated name"),
+                span,
+                [(span, "generated names will be renamed to avoid this one".to_string())],
+            );
+        }
+
+        if self.macro_args.iter().any(|(args, _)| args.iter().any(|arg| arg == name)) {
+            None
+        } else {
+            Some(self.variables.entry(name).or_default())
+        }
+    }
+
+    fn finish(mut self) -> Vec<Diagnostic> {
+        let variables = std::mem::take(&mut self.variables);
+
+        for (name, usage) in &variables {
+            if usage.assigned.is_empty() {
+                self.warn(
+                    Lint::UndefinedVariable,
+                    format!("Variable `{name}` is read but never assigned"),
+                    usage.read[0],
+                    usage.read.iter().map(|&span| (span, "read here".to_string())),
+                );
+            } else if usage.read.is_empty() {
+                self.warn(
+                    Lint::UnusedVariable,
+                    format!("Variable `{name}` is assigned but never read"),
+                    usage.assigned[0],
+                    usage.assigned.iter().map(|&span| (span, "assigned here".to_string())),
+                );
+            }
+        }
+
+        for (name, span) in std::mem::take(&mut self.shadowing) {
+            if let Some(usage) = variables.get(name) {
+                let other = usage.assigned.iter().chain(&usage.read).min_by_key(|span| span.start);
+
+                self.warn(
+                    Lint::Shadowing,
+                    format!("Macro argument `{name}` shadows the variable `{name}`"),
+                    span,
+                    [(span, format!("`{name}` is an argument of this macro"))]
+                        .into_iter()
+                        .chain(other.map(|&span| (span, "variable used here".to_string()))),
+                );
+            }
+        }
+
+        self.warnings.sort_by_key(|warning| warning.span.start);
+        self.warnings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn warnings(code: &str, levels: &LintLevels) -> Vec<String> {
+        let ast = crate::parser::parse_test_code(code);
+        check(&ast, levels, "_scgt_").into_iter().map(|warning| warning.message).collect()
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            warnings("a!3;b\nc.d!4", &LintLevels::default()),
+            [
+                "Variable `a` is assigned but never read",
+                "Variable `b` is read but never assigned",
+                "Variable `c` is read but never assigned",
+            ],
+        );
+    }
+
+    #[test]
+    fn macro_args() {
+        assert_eq!(warnings("fX$x", &LintLevels::default()), ["Variable `f` is assigned but never read"]);
+        assert_eq!(
+            warnings("x!3;$x\n$X$x", &LintLevels::default()),
+            ["Macro argument `x` shadows the variable `x`"],
+        );
+        assert_eq!(
+            warnings("$XX$x", &LintLevels::default()),
+            ["Macro argument `x` shadows the argument `x` of an enclosing macro"],
+        );
+    }
+
+    #[test]
+    fn reserved_and_loop_variables() {
+        assert_eq!(
+            warnings("__scgt_loop_1!I;$__scgt_loop_1\nL$I", &LintLevels::default()),
+            [
+                "Identifier `_scgt_loop_1` has the form of a generated name",
+                "Loop variable `I` used outside of a loop",
+                "Identifier `_scgt_loop_1` has the form of a generated name",
+            ],
+        );
+    }
+
+    #[test]
+    fn lint_levels() {
+        let mut levels = LintLevels::default();
+        levels.allow(Lint::All);
+        levels.warn(Lint::UnusedVariable);
+
+        assert_eq!(warnings("a!b", &levels), ["Variable `a` is assigned but never read"]);
+    }
+}